@@ -1,18 +1,36 @@
-use std::{collections::HashMap, fs};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fs,
+    path::Path,
+    time::Duration,
+};
 
 use bytes::Bytes;
+use futures::SinkExt;
 use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
 use iced::{
-    executor,
+    clipboard, event, executor,
+    keyboard::{self, key::Named, Key, Modifiers},
+    theme, time,
     widget::{self, column, image::Handle, row, text_editor},
-    Application, Command, Length, Theme,
+    window, Application, Color, Command, Length, Subscription, Theme,
 };
 use native_dialog::FileDialog;
+use serde::{Deserialize, Serialize};
+use unicode_segmentation::UnicodeSegmentation;
 use uuid::Uuid;
 
 use crate::{
-    models::{CardInDeck, Deck, IndexedCard},
-    mtg::{self, CardErrorInsight},
+    image_cache::ImageCache,
+    models::{
+        Card, CardCondition, CardInDeck, Deck, DeckFile, GameFormat, ImageQuality, IndexedCard,
+        Legality, PaperSize, PriceSnapshot,
+    },
+    mtg::{self, AnalyzedDecklist},
+    proxy,
+    settings::{Setting, Settings},
+    stats::{self, Allocation, CardType, CollectionCard, HandSimulation, SortMode},
+    storage,
 };
 
 const DEFAULT_IMAGE: &[u8] = include_bytes!("../assets/copy_token.png");
@@ -23,38 +41,323 @@ pub struct App {
     deck_input_content: text_editor::Content,
     deck_output: String,
     deck_in_progress: Option<Vec<CardInDeck>>,
+    sideboard_in_progress: Vec<CardInDeck>,
+    deck_diff: Vec<CardDiff>,
     deck_name: String,
     search_text: String,
+    search_generation: u64,
+    search_scope: SearchScope,
+    /// Modifier keys currently held, for shortcuts inside the decklist editor.
+    modifiers: Modifiers,
+    window_size: Option<(u32, u32)>,
+    analysis_format: Option<GameFormat>,
+    /// Generation of the running decklist analysis, if any.
+    analysis: Option<u64>,
+    analysis_generation: u64,
     card_index: Vec<IndexedCard>,
     search_result: Vec<IndexedCard>,
-    image_cache: HashMap<String, Bytes>,
+    search_limit: usize,
+    /// Cards matching the search, including those past `search_limit`.
+    search_match_count: usize,
+    image_cache: ImageCache,
+    loading_images: HashSet<String>,
+    failed_images: HashSet<String>,
+    /// Card shown enlarged next to the deck.
+    zoomed_card: Option<String>,
+    /// Downloaded large images, `None` for ones that failed.
+    large_images: HashMap<String, Option<Bytes>>,
     default_image: Bytes,
+    simulation_target: Option<String>,
+    simulation_turn: u8,
+    simulation: Option<HandSimulation>,
+    settings: Settings,
+    card_table_filter: String,
+    card_table_sort: CardTableColumn,
+    card_table_ascending: bool,
+    shopping_type_filter: Option<CardType>,
+    focus_mode: bool,
+    tag_input: String,
+    deck_tag_input: String,
+    deck_tag_filter: Option<String>,
+    token_input: String,
+    new_card_input: String,
+    new_card_quantity: i32,
+    new_card_output: String,
+    deck_filter: String,
+    deck_missing_only: bool,
+    deck_sort: SortMode,
+    /// Deck waiting for the user to confirm its deletion.
+    confirm_delete: Option<Uuid>,
+    undo_stack: VecDeque<HashMap<Uuid, Deck>>,
+    redo_stack: Vec<HashMap<Uuid, Deck>>,
+    /// Field the newest undo entry was made for, while it's still being typed in.
+    last_edited_field: Option<EditedField>,
+    /// Deck changes not written to disk yet.
+    unsaved_decks: bool,
+    save_generation: u64,
+    rename_input: String,
+    editing_deck: Option<Uuid>,
+    /// Problem shown above every section until dismissed.
+    error_banner: Option<String>,
+    token_output: String,
+    correction_from: String,
+    correction_to: String,
+    price_history: Vec<PriceSnapshot>,
+    prices_updated_at: Option<i64>,
+    /// Last failed background price refresh, retried after `PRICE_REFRESH_RETRY`.
+    price_refresh_failed_at: Option<i64>,
+    refreshing_prices: bool,
+    owned: HashMap<String, i32>,
+    allocation: Vec<Allocation>,
+    rulings: HashMap<String, Result<Vec<String>, String>>,
 }
 
-#[derive(Debug, Clone)]
+/// Decks the deck builder searches.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum SearchScope {
+    #[default]
+    AllDecks,
+    Deck(Uuid, String),
+}
+
+impl std::fmt::Display for SearchScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SearchScope::AllDecks => write!(f, "All decks"),
+            SearchScope::Deck(_, name) => write!(f, "{}", name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Section {
     Decks,
     AddDeck,
     ViewDeck(Uuid),
     BuildDecks,
+    CardTable,
+    CardDetail(Uuid, String),
+    ShoppingList,
+    Buildable,
+    Overcommitted,
+    Collection,
+    Settings,
+}
+
+/// Where the user left off, restored on the next start.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct UiState {
+    pub section: Option<Section>,
+    pub window_size: Option<(u32, u32)>,
+}
+
+impl AppMessage {
+    /// Whether handling this message can change the decks, which are saved
+    /// to disk afterwards.
+    fn changes_decks(&self) -> bool {
+        matches!(
+            self,
+            AppMessage::CreateDeck
+                | AppMessage::DeleteDeck(_)
+                | AppMessage::DuplicateDeck(_)
+                | AppMessage::RenameDeck(..)
+                | AppMessage::AddCard(..)
+                | AppMessage::RemoveCard(..)
+                | AppMessage::SetCardQuantity(..)
+                | AppMessage::SetCardCondition(..)
+                | AppMessage::ClearCardConditions(..)
+                | AppMessage::SetCardNote(..)
+                | AppMessage::AddCardTag(..)
+                | AppMessage::RemoveCardTag(..)
+                | AppMessage::MoveDeck(..)
+                | AppMessage::AddDeckTag(..)
+                | AppMessage::RemoveDeckTag(..)
+                | AppMessage::SetDeckFormat(..)
+                | AppMessage::AddTokenFinish(..)
+                | AppMessage::ChangeTokenQuantity(..)
+                | AppMessage::AddStapleFinish(..)
+                | AppMessage::AddNewCardFinish(..)
+                | AppMessage::AllocateOwned
+                | AppMessage::Import
+                | AppMessage::ImportMerge
+                | AppMessage::ImportProgress(_)
+                | AppMessage::RefreshImagesFinish(_)
+                | AppMessage::RefreshPricesFinish(_)
+                | AppMessage::Undo
+                | AppMessage::Redo
+        )
+    }
+
+    /// Text field this message types into. Consecutive edits of the same
+    /// field are undone together.
+    fn edited_field(&self) -> Option<EditedField> {
+        match self {
+            AppMessage::SetCardQuantity(deck_id, card_name, _) => {
+                Some(EditedField::CardQuantity(*deck_id, card_name.clone()))
+            }
+            AppMessage::SetCardNote(deck_id, card_name, _) => {
+                Some(EditedField::CardNote(*deck_id, card_name.clone()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the deck changes of this message can be undone. Background
+    /// refreshes only update Scryfall data, so they're left out.
+    fn undoable(&self) -> bool {
+        self.changes_decks()
+            && !matches!(
+                self,
+                AppMessage::RefreshImagesFinish(_)
+                    | AppMessage::RefreshPricesFinish(_)
+                    | AppMessage::Undo
+                    | AppMessage::Redo
+            )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum EditedField {
+    CardQuantity(Uuid, String),
+    CardNote(Uuid, String),
+}
+
+impl Section {
+    /// The section Escape goes back to, if this one is nested.
+    fn parent(&self) -> Option<Section> {
+        match self {
+            Section::CardDetail(deck_id, _) => Some(Section::ViewDeck(*deck_id)),
+            Section::ViewDeck(_) => Some(Section::Decks),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CardTableColumn {
+    Name,
+    Needed,
+    Owned,
+    Decks,
+    Price,
+    Set,
 }
 
 #[derive(Debug, Clone)]
 pub enum AppMessage {
     ChangeSection(Section),
     EditDeckInput(text_editor::Action),
+    ModifiersChanged(Modifiers),
+    WindowResized(u32, u32),
+    WindowCloseRequested,
     AnalyzeDeck,
-    AnalyzeDeckFinish(Vec<CardInDeck>, Vec<CardErrorInsight>),
+    AnalyzeDeckProgress(usize, usize),
+    AnalyzeDeckFinish(Result<AnalyzedDecklist, String>),
     UpdateDeckName(String),
     CreateDeck,
     ViewDeck(Uuid),
+    ViewCard(Uuid, String),
+    RequestDeleteDeck(Uuid),
+    CancelDeleteDeck,
     DeleteDeck(Uuid),
+    EditDeck(Uuid),
+    CancelEdit,
+    DuplicateDeck(Uuid),
+    UpdateRenameInput(String),
+    RenameDeck(Uuid, String),
     Search(String),
-    UpdateImageCache(String, Option<Bytes>),
+    SearchSettled(u64),
+    SaveDecks(u64),
+    SetSearchScope(SearchScope),
+    ShowMoreResults,
+    UpdateImageCache(String, Option<(String, Bytes)>),
+    RetryImage(String),
+    ShowCardDetail(String),
+    HideCardDetail,
+    LargeImageLoaded(String, Option<Bytes>),
     AddCard(Uuid, String),
     RemoveCard(Uuid, String),
+    SetCardQuantity(Uuid, String, i32),
+    DeckFromClipboard,
+    PasteDecklist,
+    SetAnalysisFormat(Option<GameFormat>),
+    DeckFromClipboardRead(Option<String>),
+    ImportDecklistFile,
     Import,
+    ImportMerge,
     Export,
+    ExportDeck(Uuid, bool),
+    ExportDecklist(Uuid),
+    ExportProxySheet(Uuid),
+    ClearImageCache,
+    ReloadDeckImages(Uuid),
+    ExportProxySheetFinish(Result<usize, String>),
+    ImportProgress(Uuid),
+    SelectSimulationTarget(String),
+    UpdateSimulationTurn(u8),
+    SimulateHands(Uuid),
+    /// Sets the condition of one collected copy, counted from 0.
+    SetCardCondition(Uuid, String, usize, CardCondition),
+    ClearCardConditions(Uuid, String),
+    SetCardNote(Uuid, String, String),
+    UpdateSetting(Setting),
+    SaveSettings,
+    UpdateCorrectionFrom(String),
+    UpdateCorrectionTo(String),
+    AddNameCorrection,
+    RefreshImagesFinish(HashMap<String, Vec<String>>),
+    FilterCardTable(String),
+    SortCardTable(CardTableColumn),
+    FilterShoppingList(Option<CardType>),
+    ToggleFocusMode,
+    UpdateTagInput(String),
+    AddCardTag(Uuid, String, String),
+    RemoveCardTag(Uuid, String, String),
+    UpdateDeckTagInput(String),
+    AddDeckTag(Uuid, String),
+    RemoveDeckTag(Uuid, String),
+    FilterDecksByTag(Option<String>),
+    RecordPriceSnapshot,
+    SetOwned(String, String),
+    AllocateOwned,
+    RulingsLoaded(String, Result<Vec<String>, String>),
+    SetDeckFormat(Uuid, Option<GameFormat>),
+    UpdateTokenInput(String),
+    AddToken(Uuid),
+    AddTokenFinish(Uuid, Result<Card, String>),
+    ChangeTokenQuantity(Uuid, String, i32),
+    AddStaple(Uuid, String),
+    UpdateNewCardInput(String),
+    UpdateNewCardQuantity(i32),
+    AddNewCardToDeck(Uuid, String),
+    AddNewCardFinish(Uuid, Result<CardInDeck, String>),
+    AddStapleFinish(Uuid, Result<Card, String>),
+    FilterDeck(String),
+    SortDeck(SortMode),
+    ToggleDeckMissingOnly(bool),
+    NavigateBack,
+    PriceRefreshTick,
+    RefreshPrices,
+    RefreshPricesFinish(Result<mtg::CollectionLookup, String>),
+    Undo,
+    Redo,
+    ShowError(String),
+    DismissError,
+    ExportCollectionCsv,
+    MoveDeck(Uuid, Direction),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Up,
+    Down,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CardDiff {
+    Added(String, i32),
+    Removed(String, i32),
+    Changed(String, i32, i32),
 }
 
 type AppElement<'a> = iced::Element<'a, AppMessage, Theme, iced::Renderer>;
@@ -63,15 +366,40 @@ impl Application for App {
     type Executor = executor::Default;
     type Message = AppMessage;
     type Theme = Theme;
-    type Flags = ();
+    type Flags = UiState;
 
-    fn new(_flags: Self::Flags) -> (Self, iced::Command<Self::Message>) {
-        (
-            Self {
-                ..Default::default()
-            },
-            Command::none(),
-        )
+    fn new(flags: Self::Flags) -> (Self, iced::Command<Self::Message>) {
+        let settings = storage::load_settings();
+        mtg::set_rate_limit(settings.requests_per_second);
+        if let Some(cache) = storage::load_json(storage::LOOKUP_CACHE_FILE) {
+            mtg::load_lookup_cache(cache);
+        }
+        let mut app = Self {
+            decks: storage::load_json(storage::DECKS_FILE).unwrap_or_default(),
+            image_cache: ImageCache::new(settings.image_cache_budget()),
+            price_history: storage::load_price_history(),
+            owned: storage::load_json(storage::OWNED_FILE).unwrap_or_default(),
+            prices_updated_at: storage::load_json(storage::PRICE_REFRESH_FILE),
+            search_limit: settings.search_results(),
+            settings,
+            ..Default::default()
+        };
+        app.rebuild_card_index();
+        app.window_size = flags.window_size;
+
+        // Sections of decks deleted since then can't be restored.
+        let restore = match flags.section {
+            Some(Section::ViewDeck(id)) if app.decks.contains_key(&id) => AppMessage::ViewDeck(id),
+            Some(Section::CardDetail(id, name)) if app.decks.contains_key(&id) => {
+                AppMessage::ViewCard(id, name)
+            }
+            Some(Section::ViewDeck(_) | Section::CardDetail(..)) | None => {
+                AppMessage::ChangeSection(Section::Decks)
+            }
+            Some(section) => AppMessage::ChangeSection(section),
+        };
+        let command = app.update(restore);
+        (app, command)
     }
 
     fn title(&self) -> String {
@@ -79,24 +407,241 @@ impl Application for App {
     }
 
     fn theme(&self) -> Self::Theme {
-        Theme::Dark
+        if self.settings.dark_theme {
+            Theme::Dark
+        } else {
+            Theme::Light
+        }
     }
 
     fn update(&mut self, message: Self::Message) -> iced::Command<Self::Message> {
+        let changes_decks = message.changes_decks();
+        let edited_field = message.edited_field();
+        // Further typing into the field edited last is covered by the undo
+        // entry its first edit made.
+        let same_field = edited_field.is_some() && edited_field == self.last_edited_field;
+        let before = (message.undoable() && !same_field).then(|| self.decks.clone());
+        let command = self.handle_message(message);
+        if let Some(before) = before.filter(|d| *d != self.decks) {
+            if self.undo_stack.len() == UNDO_LIMIT {
+                self.undo_stack.pop_front();
+            }
+            self.undo_stack.push_back(before);
+            self.redo_stack.clear();
+            self.last_edited_field = edited_field;
+        } else if changes_decks && !same_field {
+            self.last_edited_field = None;
+        }
+        if changes_decks {
+            // Saved once changes settle, so typing into a field doesn't
+            // rewrite the file on every keystroke.
+            self.unsaved_decks = true;
+            self.save_generation += 1;
+            let generation = self.save_generation;
+            let save = Command::perform(
+                async move { tokio::time::sleep(SAVE_DEBOUNCE).await },
+                move |_| AppMessage::SaveDecks(generation),
+            );
+            return Command::batch([command, save]);
+        }
+        command
+    }
+
+    fn subscription(&self) -> Subscription<Self::Message> {
+        let mut subscriptions = vec![
+            keyboard::on_key_press(handle_key_press),
+            event::listen_with(|event, _| match event {
+                iced::Event::Keyboard(keyboard::Event::ModifiersChanged(m)) => {
+                    Some(AppMessage::ModifiersChanged(m))
+                }
+                iced::Event::Window(_, window::Event::Resized { width, height }) => {
+                    Some(AppMessage::WindowResized(width, height))
+                }
+                iced::Event::Window(_, window::Event::CloseRequested) => {
+                    Some(AppMessage::WindowCloseRequested)
+                }
+                _ => None,
+            }),
+        ];
+        if self.settings.auto_refresh_prices {
+            subscriptions
+                .push(time::every(PRICE_REFRESH_CHECK).map(|_| AppMessage::PriceRefreshTick));
+        }
+        if let Some(generation) = self.analysis {
+            let mut options = self.settings.decklist_options();
+            options.format = self.analysis_format;
+            subscriptions.push(analyze_decklist(
+                generation,
+                self.deck_input_content.text(),
+                options,
+            ));
+        }
+
+        Subscription::batch(subscriptions)
+    }
+
+    fn view(&self) -> iced::Element<'_, Self::Message, Self::Theme, iced::Renderer> {
+        let content = match self.section {
+            Section::Decks => view_decks(self),
+            Section::AddDeck => view_add_deck(self),
+            Section::ViewDeck(deck_id) => view_deck(self, deck_id),
+            Section::BuildDecks => view_deck_builder(self),
+            Section::CardTable => view_card_table(self),
+            Section::ShoppingList => view_shopping_list(self),
+            Section::Buildable => view_buildable(self),
+            Section::Overcommitted => view_overcommitted(self),
+            Section::CardDetail(deck_id, ref card_name) => {
+                view_card_detail(self, deck_id, card_name)
+            }
+            Section::Settings => view_settings(self),
+            Section::Collection => view_collection(self),
+        };
+
+        if self.focus_mode {
+            let btn_exit_focus = widget::button("Exit focus")
+                .style(theme::Button::Text)
+                .on_press(AppMessage::ToggleFocusMode);
+            return column!(btn_exit_focus, view_error_banner(self), content).into();
+        }
+
+        let toolbar = row!(
+            nav_button(self, "Decks", Section::Decks),
+            nav_button(self, "New deck", Section::AddDeck),
+            nav_button(self, "Build", Section::BuildDecks),
+            nav_button(self, "Cards", Section::CardTable),
+            nav_button(self, "Collection", Section::Collection),
+            nav_button(self, "Missing", Section::ShoppingList),
+            nav_button(self, "Buildable", Section::Buildable),
+            nav_button(self, "Overcommitted", Section::Overcommitted),
+            nav_button(self, "Settings", Section::Settings),
+            widget::button("Undo")
+                .style(theme::Button::Text)
+                .on_press_maybe((!self.undo_stack.is_empty()).then_some(AppMessage::Undo)),
+            widget::button("Redo")
+                .style(theme::Button::Text)
+                .on_press_maybe((!self.redo_stack.is_empty()).then_some(AppMessage::Redo)),
+            widget::button("Focus")
+                .style(theme::Button::Text)
+                .on_press(AppMessage::ToggleFocusMode),
+            widget::button(if self.settings.dark_theme {
+                "Light"
+            } else {
+                "Dark"
+            })
+            .style(theme::Button::Text)
+            .on_press(AppMessage::UpdateSetting(Setting::DarkTheme(
+                !self.settings.dark_theme
+            ))),
+        )
+        .spacing(5);
+
+        column!(toolbar, view_error_banner(self), content)
+            .spacing(10)
+            .into()
+    }
+}
+
+impl App {
+    fn save_decks(&mut self) {
+        if !self.unsaved_decks {
+            return;
+        }
+        self.unsaved_decks = false;
+        if let Err(e) = storage::save_json(storage::DECKS_FILE, &self.decks) {
+            eprintln!("could not save decks: {}", e);
+        }
+    }
+
+    fn rebuild_card_index(&mut self) {
+        self.card_index = self
+            .decks
+            .iter()
+            .flat_map(|(id, deck)| build_card_index(*id, &deck.cards))
+            .collect();
+    }
+
+    /// Replaces every deck, e.g. with an undo snapshot, and forgets search
+    /// state pointing at decks that no longer exist.
+    fn restore_decks(&mut self, decks: HashMap<Uuid, Deck>) -> HashMap<Uuid, Deck> {
+        let current = std::mem::replace(&mut self.decks, decks);
+        self.rebuild_card_index();
+        self.search_result
+            .retain(|c| self.decks.contains_key(&c.deck_id));
+        if matches!(self.search_scope, SearchScope::Deck(id, _) if !self.decks.contains_key(&id)) {
+            self.search_scope = SearchScope::AllDecks;
+        }
+        current
+    }
+
+    fn handle_message(&mut self, message: AppMessage) -> Command<AppMessage> {
         match message {
             AppMessage::ChangeSection(section) => {
                 self.section = section;
+
+                if self.section == Section::Collection {
+                    let mut cards = self.card_index.clone();
+                    cards.sort_by(|a, b| a.name.cmp(&b.name));
+                    cards.dedup_by(|a, b| a.name == b.name);
+                    return Command::batch(
+                        cards
+                            .iter()
+                            .map(|c| self.fetch_image(&c.name, &c.img, &c.img_fallbacks)),
+                    );
+                }
+            }
+            AppMessage::EditDeckInput(text_editor::Action::Edit(text_editor::Edit::Enter))
+                if self.modifiers.command() =>
+            {
+                return self.update(AppMessage::AnalyzeDeck);
             }
             AppMessage::EditDeckInput(action) => self.deck_input_content.perform(action),
+            AppMessage::ModifiersChanged(modifiers) => self.modifiers = modifiers,
+            AppMessage::WindowResized(width, height) => self.window_size = Some((width, height)),
+            AppMessage::SaveDecks(generation) => {
+                if generation == self.save_generation {
+                    self.save_decks();
+                }
+            }
+            AppMessage::WindowCloseRequested => {
+                self.save_decks();
+                let state = UiState {
+                    section: Some(self.section.clone()),
+                    window_size: self.window_size,
+                };
+                if let Err(e) = storage::save_json(storage::UI_STATE_FILE, &state) {
+                    eprintln!("could not save window state: {}", e);
+                }
+                return window::close(window::Id::MAIN);
+            }
             AppMessage::AnalyzeDeck => {
                 self.deck_output = "Analyzing...".to_owned();
-                let input = self.deck_input_content.text();
-                return iced::Command::perform(
-                    async move { mtg::process_decklist(input).await },
-                    |res| AppMessage::AnalyzeDeckFinish(res.0, res.1),
-                );
+                self.analysis_generation += 1;
+                self.analysis = Some(self.analysis_generation);
+            }
+            AppMessage::AnalyzeDeckProgress(resolved, total) => {
+                if self.analysis.is_some() {
+                    self.deck_output = format!("Resolved {}/{}…", resolved, total);
+                }
             }
-            AppMessage::AnalyzeDeckFinish(cards_in_deck, errors) => {
+            AppMessage::AnalyzeDeckFinish(result) => {
+                self.analysis = None;
+                let AnalyzedDecklist {
+                    cards: cards_in_deck,
+                    sideboard,
+                    errors,
+                } = match result {
+                    Ok(r) => r,
+                    Err(e) => {
+                        self.deck_output = format!("{}\n", e);
+                        return iced::Command::none();
+                    }
+                };
+                if let Err(e) =
+                    storage::save_json(storage::LOOKUP_CACHE_FILE, &mtg::lookup_cache_snapshot())
+                {
+                    eprintln!("could not save lookup cache: {}", e);
+                }
+
                 self.deck_output = String::default();
                 let total_unique = cards_in_deck.len();
                 let total_count = cards_in_deck
@@ -107,32 +652,80 @@ impl Application for App {
                     "Found {} unique cards ({} total cards).\n",
                     total_unique, total_count
                 ));
-                if errors.len() > 0 {
+                if !sideboard.is_empty() {
+                    self.deck_output.push_str(&format!(
+                        "Sideboard: {} cards.\n",
+                        stats::card_count(&sideboard)
+                    ));
+                }
+                if !errors.is_empty() {
                     self.deck_output.push_str("Errors:\n");
                 }
 
-                for error in errors {
+                for error in &errors {
                     self.deck_output
                         .push_str(&format!("{}: {}\n", error.card_name, error.error))
                 }
+                if errors.iter().any(|e| e.kind == mtg::ErrorKind::Network) {
+                    self.deck_output
+                        .push_str("Some cards couldn't be looked up, analyze again to retry.\n");
+                }
 
-                self.deck_in_progress = Some(cards_in_deck)
+                self.deck_diff = match &self.deck_in_progress {
+                    Some(previous) => diff_cards(previous, &cards_in_deck),
+                    None => Vec::new(),
+                };
+                self.deck_in_progress = Some(cards_in_deck);
+                self.sideboard_in_progress = sideboard;
             }
             AppMessage::UpdateDeckName(name) => self.deck_name = name,
             AppMessage::CreateDeck => {
-                let deck_id = Uuid::new_v4();
-                self.decks.insert(
-                    deck_id,
-                    Deck {
-                        name: self.deck_name.clone(),
-                        cards: self.deck_in_progress.clone().expect("oops"),
-                    },
-                );
-                self.card_index.append(&mut build_card_index(
-                    deck_id,
-                    self.deck_in_progress.as_ref().expect("oops"),
-                ));
-                self.deck_in_progress = None;
+                if self.deck_name.trim().is_empty() {
+                    return iced::Command::none();
+                }
+                let cards = match self.deck_in_progress.take() {
+                    Some(c) => c,
+                    None => return iced::Command::none(),
+                };
+
+                let name = self.unique_deck_name(self.deck_name.trim(), self.editing_deck);
+                let sideboard = std::mem::take(&mut self.sideboard_in_progress);
+                let edited = self
+                    .editing_deck
+                    .take()
+                    .and_then(|id| self.decks.get_mut(&id).map(|d| (id, d)));
+
+                let deck_id = match edited {
+                    Some((deck_id, deck)) => {
+                        deck.cards = keep_progress(&deck.cards, cards);
+                        deck.sideboard = keep_progress(&deck.sideboard, sideboard);
+                        deck.name = name;
+                        self.card_index.retain(|c| c.deck_id != deck_id);
+                        self.card_index
+                            .append(&mut build_card_index(deck_id, &deck.cards));
+                        deck_id
+                    }
+                    None => {
+                        let deck_id = Uuid::new_v4();
+                        self.card_index
+                            .append(&mut build_card_index(deck_id, &cards));
+                        self.decks.insert(
+                            deck_id,
+                            Deck {
+                                name,
+                                cards,
+                                format: self.analysis_format,
+                                tokens: Vec::new(),
+                                sideboard,
+                                tags: Vec::new(),
+                                position: self.next_deck_position(),
+                            },
+                        );
+                        deck_id
+                    }
+                };
+                self.search_result.retain(|c| c.deck_id != deck_id);
+                self.deck_diff = Vec::new();
                 self.deck_output = String::new();
                 self.deck_name = String::new();
                 self.section = Section::Decks;
@@ -140,70 +733,438 @@ impl Application for App {
             }
             AppMessage::ViewDeck(id) => {
                 self.section = Section::ViewDeck(id);
+                self.deck_filter = String::new();
+                self.rename_input = self
+                    .decks
+                    .get(&id)
+                    .map(|d| d.name.clone())
+                    .unwrap_or_default();
+                self.simulation_target = None;
+                self.simulation = None;
                 if let Some(deck) = self.decks.get(&id) {
+                    let cards = deck
+                        .cards
+                        .iter()
+                        .chain(deck.sideboard.iter())
+                        .chain(deck.tokens.iter())
+                        .map(|c| c.card.clone())
+                        .collect::<Vec<_>>();
                     return Command::batch(
-                        deck.cards
+                        cards
                             .iter()
-                            .filter(|&c| self.image_cache.get(&c.card.name).is_none())
-                            .map(|c| {
-                                let card = c.card.clone();
-                                Command::perform(
-                                    async move { download_image(&card.name, &card.img).await },
-                                    |res| AppMessage::UpdateImageCache(res.0, res.1),
-                                )
-                            }),
+                            .map(|c| self.fetch_image(&c.name, &c.img, &c.img_fallbacks)),
                     );
                 }
             }
+            AppMessage::ViewCard(deck_id, card_name) => {
+                let card = self
+                    .decks
+                    .get(&deck_id)
+                    .and_then(|d| d.cards.iter().find(|c| c.card.name == card_name))
+                    .map(|c| c.card.clone());
+                self.section = Section::CardDetail(deck_id, card_name);
+                let timeout = self.settings.request_timeout();
+
+                if let Some(card) = card {
+                    let mut commands = Vec::new();
+                    if !matches!(self.rulings.get(&card.name), Some(Ok(_))) {
+                        let card = card.clone();
+                        commands.push(Command::perform(
+                            async move {
+                                let rulings = mtg::rulings(&card.name, card.id, timeout)
+                                    .await
+                                    .map_err(|e| e.to_string());
+                                (card.name, rulings)
+                            },
+                            |res| AppMessage::RulingsLoaded(res.0, res.1),
+                        ));
+                    }
+                    commands.push(self.fetch_image(&card.name, &card.img, &card.img_fallbacks));
+                    return Command::batch(commands);
+                }
+            }
+            AppMessage::SetDeckFormat(deck_id, format) => {
+                if let Some(deck) = self.decks.get_mut(&deck_id) {
+                    deck.format = format;
+                }
+            }
+            AppMessage::UpdateTokenInput(name) => self.token_input = name,
+            AppMessage::AddToken(deck_id) => {
+                let name = self.token_input.trim().to_owned();
+                if name.is_empty() {
+                    return iced::Command::none();
+                }
+                self.token_output = format!("Searching for {}...", name);
+                let timeout = self.settings.request_timeout();
+                let quality = self.settings.image_quality;
+                return Command::perform(
+                    async move {
+                        mtg::token_fuzzy(&name, timeout, quality)
+                            .await
+                            .map_err(|e| e.to_string())
+                    },
+                    move |res| AppMessage::AddTokenFinish(deck_id, res),
+                );
+            }
+            AppMessage::AddTokenFinish(deck_id, result) => {
+                let token = match result {
+                    Ok(t) => t,
+                    Err(e) => {
+                        self.token_output = e;
+                        return iced::Command::none();
+                    }
+                };
+                let deck = match self.decks.get_mut(&deck_id) {
+                    Some(d) => d,
+                    None => return iced::Command::none(),
+                };
+
+                self.token_input = String::new();
+                self.token_output = String::new();
+                match deck.tokens.iter_mut().find(|t| t.card.name == token.name) {
+                    Some(t) => t.quantity += 1,
+                    None => deck.tokens.push(CardInDeck {
+                        quantity: 1,
+                        current_quantity: 0,
+                        card: token.clone(),
+                        conditions: Vec::new(),
+                        tags: Vec::new(),
+                        note: None,
+                    }),
+                }
+
+                return self.fetch_image(&token.name, &token.img, &token.img_fallbacks);
+            }
+            AppMessage::ChangeTokenQuantity(deck_id, token_name, delta) => {
+                if let Some(deck) = self.decks.get_mut(&deck_id) {
+                    deck.tokens
+                        .iter_mut()
+                        .filter(|t| t.card.name == token_name)
+                        .for_each(|t| t.quantity += delta);
+                    deck.tokens.retain(|t| t.quantity > 0);
+                }
+            }
+            AppMessage::AddStaple(deck_id, card_name) => {
+                let timeout = self.settings.request_timeout();
+                let quality = self.settings.image_quality;
+                return Command::perform(
+                    async move {
+                        mtg::card_named(&card_name, timeout, quality)
+                            .await
+                            .map_err(|e| e.to_string())
+                    },
+                    move |res| AppMessage::AddStapleFinish(deck_id, res),
+                );
+            }
+            AppMessage::AddStapleFinish(deck_id, result) => {
+                let card = match result {
+                    Ok(c) => c,
+                    Err(e) => {
+                        return self.update(AppMessage::ShowError(format!(
+                            "Could not add staple: {}",
+                            e
+                        )));
+                    }
+                };
+                let deck = match self.decks.get_mut(&deck_id) {
+                    Some(d) => d,
+                    None => return iced::Command::none(),
+                };
+                if deck.cards.iter().any(|c| c.card.name == card.name) {
+                    return iced::Command::none();
+                }
+
+                let card_in_deck = CardInDeck {
+                    quantity: 1,
+                    current_quantity: 0,
+                    card,
+                    conditions: Vec::new(),
+                    tags: Vec::new(),
+                    note: None,
+                };
+                self.card_index.append(&mut build_card_index(
+                    deck_id,
+                    std::slice::from_ref(&card_in_deck),
+                ));
+                let card = card_in_deck.card.clone();
+                deck.cards.push(card_in_deck);
+
+                return self.fetch_image(&card.name, &card.img, &card.img_fallbacks);
+            }
+            AppMessage::UpdateNewCardInput(name) => self.new_card_input = name,
+            AppMessage::UpdateNewCardQuantity(quantity) => self.new_card_quantity = quantity,
+            AppMessage::AddNewCardToDeck(deck_id, name) => {
+                let name = name.trim().to_owned();
+                if name.is_empty() || self.new_card_quantity <= 0 {
+                    return iced::Command::none();
+                }
+                self.new_card_output = format!("Searching for {}...", name);
+                let quantity = self.new_card_quantity;
+                let options = self.settings.decklist_options();
+                return Command::perform(
+                    async move {
+                        mtg::resolve_card(&name, quantity, &options)
+                            .await
+                            .map_err(|e| e.to_string())
+                    },
+                    move |res| AppMessage::AddNewCardFinish(deck_id, res),
+                );
+            }
+            AppMessage::AddNewCardFinish(deck_id, result) => {
+                let card_in_deck = match result {
+                    Ok(c) => c,
+                    Err(e) => {
+                        self.new_card_output = e;
+                        return iced::Command::none();
+                    }
+                };
+                let deck = match self.decks.get_mut(&deck_id) {
+                    Some(d) => d,
+                    None => return iced::Command::none(),
+                };
+                self.new_card_input = String::new();
+                self.new_card_output = String::new();
+
+                let card = card_in_deck.card.clone();
+                match deck.cards.iter_mut().find(|c| c.card.name == card.name) {
+                    Some(existing) => existing.quantity += card_in_deck.quantity,
+                    None => {
+                        self.card_index.append(&mut build_card_index(
+                            deck_id,
+                            std::slice::from_ref(&card_in_deck),
+                        ));
+                        deck.cards.push(card_in_deck);
+                    }
+                }
+
+                return self.fetch_image(&card.name, &card.img, &card.img_fallbacks);
+            }
+            AppMessage::RulingsLoaded(card_name, rulings) => {
+                self.rulings.insert(card_name, rulings);
+            }
+            AppMessage::DuplicateDeck(id) => {
+                let mut deck = match self.decks.get(&id) {
+                    Some(d) => d.clone(),
+                    None => return iced::Command::none(),
+                };
+                deck.name.push_str(" (copy)");
+                deck.position = self.next_deck_position();
+
+                let deck_id = Uuid::new_v4();
+                self.card_index
+                    .append(&mut build_card_index(deck_id, &deck.cards));
+                self.decks.insert(deck_id, deck);
+            }
+            AppMessage::EditDeck(id) => {
+                let deck = match self.decks.get(&id) {
+                    Some(d) => d,
+                    None => return iced::Command::none(),
+                };
+                self.deck_input_content = text_editor::Content::with_text(&mtg::format_decklist(
+                    &deck.cards,
+                    &deck.sideboard,
+                ));
+                self.deck_name = deck.name.clone();
+                self.deck_in_progress = Some(deck.cards.clone());
+                self.sideboard_in_progress = deck.sideboard.clone();
+                self.deck_diff = Vec::new();
+                self.deck_output = String::new();
+                self.analysis_format = deck.format;
+                self.editing_deck = Some(id);
+                self.section = Section::AddDeck;
+            }
+            AppMessage::CancelEdit => {
+                self.editing_deck = None;
+                self.deck_in_progress = None;
+                self.sideboard_in_progress = Vec::new();
+                self.deck_diff = Vec::new();
+                self.deck_output = String::new();
+                self.deck_name = String::new();
+                self.deck_input_content = text_editor::Content::new();
+            }
+            AppMessage::UpdateRenameInput(name) => self.rename_input = name,
+            AppMessage::RenameDeck(id, name) => {
+                let name = name.trim();
+                if let Some(deck) = self.decks.get_mut(&id).filter(|_| !name.is_empty()) {
+                    deck.name = name.to_owned();
+                    self.rename_input = deck.name.clone();
+                }
+            }
+            AppMessage::ExportCollectionCsv => {
+                let file = match FileDialog::new()
+                    .set_filename("collection.csv")
+                    .add_filter("CSV", &["csv"])
+                    .show_save_single_file()
+                {
+                    Ok(f) => match f {
+                        Some(f) => f,
+                        None => return iced::Command::none(),
+                    },
+                    Err(_) => return iced::Command::none(),
+                };
+
+                let mut cards = stats::aggregate_collection(&self.decks, &self.card_index);
+                cards.sort_by(|a, b| a.name.cmp(&b.name));
+                if let Err(e) = fs::write(file, stats::collection_csv(&cards)) {
+                    self.error_banner = Some(format!("Could not export collection: {}", e));
+                }
+            }
+            AppMessage::ShowError(error) => self.error_banner = Some(error),
+            AppMessage::DismissError => self.error_banner = None,
+            AppMessage::Undo => {
+                if let Some(decks) = self.undo_stack.pop_back() {
+                    let current = self.restore_decks(decks);
+                    self.redo_stack.push(current);
+                }
+            }
+            AppMessage::Redo => {
+                if let Some(decks) = self.redo_stack.pop() {
+                    let current = self.restore_decks(decks);
+                    self.undo_stack.push_back(current);
+                }
+            }
+            AppMessage::RequestDeleteDeck(id) => self.confirm_delete = Some(id),
+            AppMessage::CancelDeleteDeck => self.confirm_delete = None,
             AppMessage::DeleteDeck(id) => {
+                self.confirm_delete = None;
+                if self.editing_deck == Some(id) {
+                    self.editing_deck = None;
+                }
+                if matches!(self.search_scope, SearchScope::Deck(deck_id, _) if deck_id == id) {
+                    self.search_scope = SearchScope::AllDecks;
+                }
                 self.decks.remove(&id);
-                self.card_index = self
-                    .card_index
-                    .iter()
-                    .filter(|&c| c.deck_id != id)
-                    .cloned()
-                    .collect();
+                self.card_index.retain(|c| c.deck_id != id);
+                self.search_result.retain(|c| c.deck_id != id);
             }
             AppMessage::Search(query) => {
                 self.search_text = query;
-                self.search_result = fuzzy_top_n(&self.search_text, &self.card_index, 10);
+                self.search_limit = self.settings.search_results();
+                self.search_generation += 1;
+                let generation = self.search_generation;
+                return Command::perform(
+                    tokio::time::sleep(self.settings.search_debounce()),
+                    move |_| AppMessage::SearchSettled(generation),
+                );
+            }
+            AppMessage::ShowMoreResults => {
+                self.search_limit += self.settings.search_results();
+                return self.update(AppMessage::SearchSettled(self.search_generation));
+            }
+            AppMessage::SetSearchScope(scope) => {
+                self.search_scope = scope;
+                return self.update(AppMessage::SearchSettled(self.search_generation));
+            }
+            AppMessage::SearchSettled(generation) => {
+                // A newer keystroke is still waiting to settle.
+                if generation != self.search_generation {
+                    return iced::Command::none();
+                }
+                let scoped = match self.search_scope {
+                    SearchScope::AllDecks => self.card_index.clone(),
+                    SearchScope::Deck(deck_id, _) => self
+                        .card_index
+                        .iter()
+                        .filter(|c| c.deck_id == deck_id)
+                        .cloned()
+                        .collect(),
+                };
+                (self.search_result, self.search_match_count) =
+                    fuzzy_top_n(&self.search_text, &scoped, self.search_limit);
+                let results = self.search_result.clone();
                 return Command::batch(
-                    self.search_result
+                    results
                         .iter()
-                        .filter(|&r| self.image_cache.get(&r.name).is_none())
-                        .map(|r| {
-                            let card = r.clone();
-                            Command::perform(
-                                async move { download_image(&card.name, &card.img).await },
-                                |res| AppMessage::UpdateImageCache(res.0, res.1),
-                            )
-                        }),
+                        .map(|c| self.fetch_image(&c.name, &c.img, &c.img_fallbacks)),
                 );
             }
-            AppMessage::UpdateImageCache(name, bytes) => match bytes {
-                Some(b) => {
-                    self.image_cache.insert(name, b);
+            AppMessage::UpdateImageCache(name, image) => {
+                self.loading_images.remove(&name);
+                match image {
+                    Some((url, b)) => {
+                        self.image_cache.insert(name.clone(), b);
+                        self.remember_image_url(&name, &url);
+                    }
+                    None => {
+                        self.failed_images.insert(name);
+                    }
                 }
-                None => {}
-            },
-            AppMessage::AddCard(deck_id, card_name) => match self.decks.get_mut(&deck_id) {
-                Some(deck) => {
+            }
+            AppMessage::RetryImage(name) => {
+                let card = self
+                    .card_index
+                    .iter()
+                    .find(|c| c.name == name)
+                    .map(|c| (c.img.clone(), c.img_fallbacks.clone()))
+                    .or_else(|| {
+                        self.decks
+                            .values()
+                            .flat_map(|d| d.tokens.iter().chain(d.sideboard.iter()))
+                            .find(|c| c.card.name == name)
+                            .map(|c| (c.card.img.clone(), c.card.img_fallbacks.clone()))
+                    });
+                if let Some((img, fallbacks)) = card {
+                    return self.fetch_image(&name, &img, &fallbacks);
+                }
+            }
+            AppMessage::AddCard(deck_id, card_name) => {
+                if let Some(deck) = self.decks.get_mut(&deck_id) {
                     deck.cards
                         .iter_mut()
                         .filter(|c| c.card.name == card_name)
-                        .for_each(|c| c.current_quantity += 1);
+                        .for_each(|c| c.set_collected(c.current_quantity + 1));
                 }
-                None => {}
-            },
-            AppMessage::RemoveCard(deck_id, card_name) => match self.decks.get_mut(&deck_id) {
-                Some(deck) => {
+            }
+            AppMessage::RemoveCard(deck_id, card_name) => {
+                if let Some(deck) = self.decks.get_mut(&deck_id) {
                     deck.cards
                         .iter_mut()
                         .filter(|c| c.card.name == card_name)
-                        .for_each(|c| c.current_quantity -= 1);
+                        .for_each(|c| c.set_collected(c.current_quantity - 1));
                 }
-                None => {}
-            },
+            }
+            AppMessage::SetCardQuantity(deck_id, card_name, quantity) => {
+                if let Some(deck) = self.decks.get_mut(&deck_id) {
+                    deck.cards
+                        .iter_mut()
+                        .filter(|c| c.card.name == card_name)
+                        .for_each(|c| c.set_collected(quantity));
+                }
+            }
+            AppMessage::SetAnalysisFormat(format) => self.analysis_format = format,
+            AppMessage::DeckFromClipboard | AppMessage::PasteDecklist => {
+                return clipboard::read(AppMessage::DeckFromClipboardRead);
+            }
+            AppMessage::DeckFromClipboardRead(contents) => {
+                let decklist = match contents {
+                    Some(c) if !c.trim().is_empty() => c,
+                    _ => return iced::Command::none(),
+                };
+
+                self.deck_input_content = text_editor::Content::with_text(&decklist);
+                self.deck_in_progress = None;
+                self.section = Section::AddDeck;
+                return self.update(AppMessage::AnalyzeDeck);
+            }
+            AppMessage::ImportDecklistFile => {
+                let file = match FileDialog::new()
+                    .add_filter("Text decklist", &["txt", "dck"])
+                    .show_open_single_file()
+                {
+                    Ok(f) => match f {
+                        Some(f) => f,
+                        None => return iced::Command::none(),
+                    },
+                    Err(_) => return iced::Command::none(),
+                };
+
+                match fs::read_to_string(&file) {
+                    Ok(decklist) => {
+                        self.deck_input_content = text_editor::Content::with_text(&decklist);
+                    }
+                    Err(e) => self.error_banner = Some(format!("Could not read decklist: {}", e)),
+                }
+            }
             AppMessage::Import => {
                 let file = match FileDialog::new()
                     .add_filter("JSON", &["json"])
@@ -216,23 +1177,43 @@ impl Application for App {
                     Err(_) => return iced::Command::none(),
                 };
 
-                let json = fs::read_to_string(file).unwrap();
+                let deck_file = match read_deck_file(&file) {
+                    Ok(d) => d,
+                    Err(e) => {
+                        self.error_banner = Some(e);
+                        return iced::Command::none();
+                    }
+                };
+                self.error_banner = None;
+
+                let imported_cards = match deck_file {
+                    DeckFile::Collection(decks) => {
+                        self.decks = decks;
+                        self.search_result = Vec::new();
+                        self.card_index = Vec::new();
+                        self.search_text = String::new();
 
-                self.decks = serde_json::from_str(&json).unwrap();
-                self.search_result = Vec::new();
-                self.image_cache = HashMap::new();
-                self.card_index = Vec::new();
-                self.search_text = String::new();
+                        for deck in &self.decks {
+                            self.card_index
+                                .append(&mut build_card_index(*deck.0, &deck.1.cards));
+                        }
 
-                for deck in &self.decks {
-                    self.card_index
-                        .append(&mut build_card_index(deck.0.clone(), &deck.1.cards));
+                        let card_index = &self.card_index;
+                        self.image_cache
+                            .retain(|name| card_index.iter().any(|c| c.name == name));
+                        self.card_index.clone()
+                    }
+                    DeckFile::Single(deck) => self.merge_decks(vec![deck]),
+                };
+
+                if self.settings.refresh_images_on_import {
+                    return refresh_images(&imported_cards, &self.settings);
                 }
             }
-            AppMessage::Export => {
+            AppMessage::ImportMerge => {
                 let file = match FileDialog::new()
                     .add_filter("JSON", &["json"])
-                    .show_save_single_file()
+                    .show_open_single_file()
                 {
                     Ok(f) => match f {
                         Some(f) => f,
@@ -241,242 +1222,2426 @@ impl Application for App {
                     Err(_) => return iced::Command::none(),
                 };
 
-                let json = serde_json::to_string(&self.decks).unwrap();
-
-                _ = fs::write(file, json);
-            }
-        };
-
-        iced::Command::none()
-    }
+                let deck_file = match read_deck_file(&file) {
+                    Ok(d) => d,
+                    Err(e) => {
+                        self.error_banner = Some(e);
+                        return iced::Command::none();
+                    }
+                };
+                self.error_banner = None;
 
-    fn view(&self) -> iced::Element<'_, Self::Message, Self::Theme, iced::Renderer> {
-        let btn_decks = widget::button("Decks")
-            .width(Length::Fixed(100.))
-            .on_press(AppMessage::ChangeSection(Section::Decks));
-        let btn_newdeck = widget::button("New deck")
-            .width(Length::Fixed(100.))
-            .on_press(AppMessage::ChangeSection(Section::AddDeck));
-        let btn_buildecks = widget::button("Build")
-            .width(Length::Fixed(100.))
-            .on_press(AppMessage::ChangeSection(Section::BuildDecks));
-
-        let list_btn = column!(btn_decks, btn_newdeck, btn_buildecks);
+                let decks = match deck_file {
+                    DeckFile::Collection(decks) => decks.into_values().collect(),
+                    DeckFile::Single(deck) => vec![deck],
+                };
+                let imported_cards = self.merge_decks(decks);
 
-        let content = match self.section {
-            Section::Decks => view_decks(self),
-            Section::AddDeck => view_add_deck(self),
-            Section::ViewDeck(deck_id) => view_deck(self, deck_id),
-            Section::BuildDecks => view_deck_builder(self),
+                if self.settings.refresh_images_on_import {
+                    return refresh_images(&imported_cards, &self.settings);
+                }
+            }
+            AppMessage::Export => {
+                let file = match FileDialog::new()
+                    .add_filter("JSON", &["json"])
+                    .show_save_single_file()
+                {
+                    Ok(f) => match f {
+                        Some(f) => f,
+                        None => return iced::Command::none(),
+                    },
+                    Err(_) => return iced::Command::none(),
+                };
+
+                let result = serde_json::to_string(&self.decks)
+                    .map_err(|e| e.to_string())
+                    .and_then(|json| fs::write(file, json).map_err(|e| e.to_string()));
+                if let Err(e) = result {
+                    self.error_banner = Some(format!("Could not export decks: {}", e));
+                }
+            }
+            AppMessage::ExportDeck(deck_id, include_progress) => {
+                let mut deck = match self.decks.get(&deck_id) {
+                    Some(d) => d.clone(),
+                    None => return iced::Command::none(),
+                };
+                if !include_progress {
+                    deck.cards
+                        .iter_mut()
+                        .chain(deck.tokens.iter_mut())
+                        .chain(deck.sideboard.iter_mut())
+                        .for_each(|c| c.set_collected(0));
+                }
+
+                let file = match FileDialog::new()
+                    .set_filename(&deck.name)
+                    .add_filter("JSON", &["json"])
+                    .add_filter("Text decklist", &["txt"])
+                    .show_save_single_file()
+                {
+                    Ok(f) => match f {
+                        Some(f) => f,
+                        None => return iced::Command::none(),
+                    },
+                    Err(_) => return iced::Command::none(),
+                };
+
+                let contents = match file.extension().and_then(|e| e.to_str()) {
+                    Some("txt") => Ok(mtg::format_decklist(&deck.cards, &deck.sideboard)),
+                    _ => serde_json::to_string(&deck).map_err(|e| e.to_string()),
+                };
+                let result = contents.and_then(|c| fs::write(file, c).map_err(|e| e.to_string()));
+                if let Err(e) = result {
+                    self.error_banner = Some(format!("Could not export {}: {}", deck.name, e));
+                }
+            }
+            AppMessage::ExportDecklist(deck_id) => {
+                let deck = match self.decks.get(&deck_id) {
+                    Some(d) => d,
+                    None => return iced::Command::none(),
+                };
+
+                let file = match FileDialog::new()
+                    .set_filename(&format!("{}.txt", deck.name))
+                    .add_filter("Text decklist", &["txt"])
+                    .show_save_single_file()
+                {
+                    Ok(f) => match f {
+                        Some(f) => f,
+                        None => return iced::Command::none(),
+                    },
+                    Err(_) => return iced::Command::none(),
+                };
+
+                if let Err(e) = fs::write(file, mtg::format_decklist(&deck.cards, &deck.sideboard))
+                {
+                    self.error_banner = Some(format!("Could not export {}: {}", deck.name, e));
+                }
+            }
+            AppMessage::ExportProxySheet(deck_id) => {
+                let deck = match self.decks.get(&deck_id) {
+                    Some(d) => d,
+                    None => return iced::Command::none(),
+                };
+
+                let file = match FileDialog::new()
+                    .set_filename(&format!("{} proxies.png", deck.name))
+                    .add_filter("PNG image", &["png"])
+                    .show_save_single_file()
+                {
+                    Ok(f) => match f {
+                        Some(f) => f,
+                        None => return iced::Command::none(),
+                    },
+                    Err(_) => return iced::Command::none(),
+                };
+
+                let cards = deck
+                    .cards
+                    .iter()
+                    .map(|c| (c.card.clone(), c.quantity))
+                    .collect();
+                let timeout = self.settings.request_timeout();
+                let paper = self.settings.paper_size;
+                return Command::perform(
+                    export_proxy_sheet(cards, timeout, paper, file),
+                    AppMessage::ExportProxySheetFinish,
+                );
+            }
+            AppMessage::ExportProxySheetFinish(result) => {
+                if let Err(e) = result {
+                    self.error_banner = Some(format!("Could not export proxies: {}", e));
+                }
+            }
+            AppMessage::ClearImageCache => {
+                self.image_cache.clear();
+                self.large_images.clear();
+                self.failed_images.clear();
+                if let Err(e) = storage::clear_image_cache() {
+                    self.error_banner = Some(format!("Could not delete saved images: {}", e));
+                }
+            }
+            AppMessage::ReloadDeckImages(deck_id) => {
+                let cards = match self.decks.get(&deck_id) {
+                    Some(deck) => deck
+                        .cards
+                        .iter()
+                        .chain(deck.sideboard.iter())
+                        .chain(deck.tokens.iter())
+                        .map(|c| c.card.clone())
+                        .collect::<Vec<_>>(),
+                    None => return iced::Command::none(),
+                };
+
+                let mut failed = 0;
+                for card in &cards {
+                    self.image_cache.remove(&card.name);
+                    self.large_images.remove(&card.name);
+                    let urls = std::iter::once(&card.img)
+                        .chain(card.img_fallbacks.iter())
+                        .chain(card.back_img.iter())
+                        .chain(card.large_img.iter());
+                    for url in urls.filter(|u| !u.is_empty()) {
+                        if storage::remove_image(url).is_err() {
+                            failed += 1;
+                        }
+                    }
+                }
+                if failed > 0 {
+                    self.error_banner = Some(format!(
+                        "Could not delete {} saved images, they may not be reloaded",
+                        failed
+                    ));
+                }
+
+                return Command::batch(
+                    cards
+                        .iter()
+                        .map(|c| self.fetch_image(&c.name, &c.img, &c.img_fallbacks)),
+                );
+            }
+            AppMessage::ImportProgress(deck_id) => {
+                let deck_name = match self.decks.get(&deck_id) {
+                    Some(d) => d.name.clone(),
+                    None => return iced::Command::none(),
+                };
+
+                let file = match FileDialog::new()
+                    .add_filter("JSON", &["json"])
+                    .show_open_single_file()
+                {
+                    Ok(f) => match f {
+                        Some(f) => f,
+                        None => return iced::Command::none(),
+                    },
+                    Err(_) => return iced::Command::none(),
+                };
+
+                let deck_file = match read_deck_file(&file) {
+                    Ok(d) => d,
+                    Err(e) => {
+                        self.error_banner = Some(e);
+                        return iced::Command::none();
+                    }
+                };
+                self.error_banner = None;
+
+                let source = match deck_file {
+                    DeckFile::Single(deck) => Some(deck),
+                    DeckFile::Collection(decks) => {
+                        decks.into_values().find(|d| d.name == deck_name)
+                    }
+                };
+
+                if let (Some(source), Some(deck)) = (source, self.decks.get_mut(&deck_id)) {
+                    import_progress(&mut deck.cards, &source.cards);
+                    import_progress(&mut deck.sideboard, &source.sideboard);
+                }
+            }
+            AppMessage::SetCardNote(deck_id, card_name, note) => {
+                let note = Some(note).filter(|n| !n.trim().is_empty());
+                if let Some(deck) = self.decks.get_mut(&deck_id) {
+                    deck.cards
+                        .iter_mut()
+                        .filter(|c| c.card.name == card_name)
+                        .for_each(|c| c.note = note.clone());
+                }
+            }
+            AppMessage::SetCardCondition(deck_id, card_name, copy, condition) => {
+                if let Some(deck) = self.decks.get_mut(&deck_id) {
+                    deck.cards
+                        .iter_mut()
+                        .filter(|c| c.card.name == card_name)
+                        .for_each(|c| c.set_copy_condition(copy, Some(condition)));
+                }
+            }
+            AppMessage::ClearCardConditions(deck_id, card_name) => {
+                if let Some(deck) = self.decks.get_mut(&deck_id) {
+                    deck.cards
+                        .iter_mut()
+                        .filter(|c| c.card.name == card_name)
+                        .for_each(|c| c.conditions.clear());
+                }
+            }
+            AppMessage::UpdateCorrectionFrom(name) => self.correction_from = name,
+            AppMessage::UpdateCorrectionTo(name) => self.correction_to = name,
+            AppMessage::AddNameCorrection => {
+                if self.correction_from.trim().is_empty() || self.correction_to.trim().is_empty() {
+                    return iced::Command::none();
+                }
+                let from = std::mem::take(&mut self.correction_from);
+                let to = std::mem::take(&mut self.correction_to);
+                return self.update(AppMessage::UpdateSetting(Setting::AddNameCorrection(
+                    from, to,
+                )));
+            }
+            AppMessage::UpdateSetting(setting) => {
+                // Sliders are saved once released rather than on every step.
+                let dragged = setting.is_dragged();
+                self.settings.apply(setting);
+                mtg::set_rate_limit(self.settings.requests_per_second);
+                self.image_cache
+                    .set_budget(self.settings.image_cache_budget());
+                if !dragged {
+                    return self.update(AppMessage::SaveSettings);
+                }
+            }
+            AppMessage::SaveSettings => {
+                if let Err(e) = storage::save_settings(&self.settings) {
+                    eprintln!("could not save settings: {}", e);
+                }
+            }
+            AppMessage::RefreshImagesFinish(urls) => {
+                for card in self.decks.values_mut().flat_map(|d| d.cards.iter_mut()) {
+                    if let Some((img, fallbacks)) =
+                        urls.get(&card.card.name).and_then(|u| u.split_first())
+                    {
+                        card.card.img = img.clone();
+                        card.card.img_fallbacks = fallbacks.to_vec();
+                    }
+                }
+                for card in self.card_index.iter_mut() {
+                    if let Some((img, fallbacks)) =
+                        urls.get(&card.name).and_then(|u| u.split_first())
+                    {
+                        card.img = img.clone();
+                        card.img_fallbacks = fallbacks.to_vec();
+                    }
+                }
+            }
+            AppMessage::FilterCardTable(filter) => self.card_table_filter = filter,
+            AppMessage::SortCardTable(column) => {
+                if self.card_table_sort == column {
+                    self.card_table_ascending = !self.card_table_ascending;
+                } else {
+                    self.card_table_sort = column;
+                    self.card_table_ascending = true;
+                }
+            }
+            AppMessage::FilterShoppingList(card_type) => self.shopping_type_filter = card_type,
+            AppMessage::ToggleFocusMode => self.focus_mode = !self.focus_mode,
+            AppMessage::FilterDeck(filter) => self.deck_filter = filter,
+            AppMessage::SortDeck(mode) => self.deck_sort = mode,
+            AppMessage::ToggleDeckMissingOnly(missing_only) => {
+                self.deck_missing_only = missing_only
+            }
+            AppMessage::ShowCardDetail(name) => {
+                self.zoomed_card = Some(name.clone());
+                if self.large_images.contains_key(&name) {
+                    return Command::none();
+                }
+                let card = match self
+                    .decks
+                    .values()
+                    .flat_map(|d| d.cards.iter().chain(d.sideboard.iter()))
+                    .find(|c| c.card.name == name)
+                {
+                    Some(c) => c.card.clone(),
+                    None => return Command::none(),
+                };
+                // Cards saved before large images were tracked fall back to
+                // their thumbnail.
+                let url = card.large_img.unwrap_or_else(|| card.img.clone());
+                let timeout = self.settings.request_timeout();
+                return Command::perform(
+                    async move { download_image(&name, &url, &[card.img], timeout).await },
+                    |res| AppMessage::LargeImageLoaded(res.0, res.1.map(|(_, b)| b)),
+                );
+            }
+            AppMessage::HideCardDetail => self.zoomed_card = None,
+            AppMessage::LargeImageLoaded(name, image) => {
+                self.large_images.insert(name, image);
+            }
+            AppMessage::NavigateBack if self.zoomed_card.is_some() => self.zoomed_card = None,
+            AppMessage::NavigateBack => match self.section.parent() {
+                Some(Section::ViewDeck(deck_id)) => {
+                    return self.update(AppMessage::ViewDeck(deck_id));
+                }
+                Some(section) => self.section = section,
+                None => {}
+            },
+            AppMessage::PriceRefreshTick => {
+                let now = chrono::Utc::now().timestamp();
+                let stale = self
+                    .prices_updated_at
+                    .is_none_or(|ts| now - ts >= PRICE_REFRESH_INTERVAL.as_secs() as i64);
+                let backing_off = self
+                    .price_refresh_failed_at
+                    .is_some_and(|ts| now - ts < PRICE_REFRESH_RETRY.as_secs() as i64);
+                if stale && !backing_off {
+                    return self.update(AppMessage::RefreshPrices);
+                }
+            }
+            AppMessage::RefreshPrices => {
+                if self.refreshing_prices {
+                    return iced::Command::none();
+                }
+                let mut identifiers = self
+                    .decks
+                    .values()
+                    .flat_map(|d| d.cards.iter().chain(&d.sideboard).chain(&d.tokens))
+                    .map(|c| mtg::CardIdentifier {
+                        name: c.card.name.clone(),
+                        id: c.card.id,
+                    })
+                    .collect::<Vec<_>>();
+                identifiers.sort_by(|a, b| a.name.cmp(&b.name));
+                identifiers.dedup_by(|a, b| a.name == b.name);
+                if identifiers.is_empty() {
+                    return iced::Command::none();
+                }
+
+                self.refreshing_prices = true;
+                let timeout = self.settings.request_timeout();
+                let quality = self.settings.image_quality;
+                return Command::perform(
+                    async move {
+                        mtg::fetch_collection(identifiers, timeout, quality)
+                            .await
+                            .map_err(|e| e.to_string())
+                    },
+                    AppMessage::RefreshPricesFinish,
+                );
+            }
+            AppMessage::RefreshPricesFinish(result) => {
+                self.refreshing_prices = false;
+                let lookup = match result {
+                    Ok(l) => l,
+                    Err(e) => {
+                        eprintln!("could not refresh prices: {}", e);
+                        self.price_refresh_failed_at = Some(chrono::Utc::now().timestamp());
+                        return iced::Command::none();
+                    }
+                };
+                for error in &lookup.errors {
+                    eprintln!("could not refresh {}: {}", error.card_name, error.error);
+                }
+
+                let cards = self.decks.values_mut().flat_map(|d| {
+                    d.cards
+                        .iter_mut()
+                        .chain(d.sideboard.iter_mut())
+                        .chain(d.tokens.iter_mut())
+                });
+                for card in cards {
+                    if let Some(fresh) = lookup.cards.get(&card.card.name) {
+                        card.card.usd_price = fresh.usd_price.clone();
+                        card.card.legalities = fresh.legalities.clone();
+                    }
+                }
+
+                let now = chrono::Utc::now().timestamp();
+                self.prices_updated_at = Some(now);
+                self.price_refresh_failed_at = None;
+                if let Err(e) = storage::save_json(storage::PRICE_REFRESH_FILE, &now) {
+                    eprintln!("could not save price refresh time: {}", e);
+                }
+            }
+            AppMessage::RecordPriceSnapshot => {
+                let cards = stats::aggregate_collection(&self.decks, &self.card_index);
+                let snapshot = PriceSnapshot {
+                    timestamp: chrono::Utc::now().timestamp(),
+                    total_value: stats::collection_value(&cards),
+                };
+                if let Err(e) = storage::append_price_snapshot(&snapshot) {
+                    eprintln!("could not save price snapshot: {}", e);
+                }
+                self.price_history.push(snapshot);
+            }
+            AppMessage::SetOwned(card_name, input) => {
+                if input.trim().is_empty() {
+                    self.owned.remove(&card_name);
+                } else if let Ok(count) = input.trim().parse::<i32>() {
+                    self.owned.insert(card_name, count.max(0));
+                } else {
+                    return iced::Command::none();
+                }
+                if let Err(e) = storage::save_json(storage::OWNED_FILE, &self.owned) {
+                    eprintln!("could not save owned cards: {}", e);
+                }
+            }
+            AppMessage::MoveDeck(id, direction) => {
+                let mut order = self.deck_order();
+                let index = match order.iter().position(|d| *d == id) {
+                    Some(i) => i,
+                    None => return iced::Command::none(),
+                };
+                let target = match direction {
+                    Direction::Up => index.checked_sub(1),
+                    Direction::Down => Some(index + 1).filter(|&i| i < order.len()),
+                };
+                if let Some(target) = target {
+                    order.swap(index, target);
+                }
+                for (position, id) in order.iter().enumerate() {
+                    if let Some(deck) = self.decks.get_mut(id) {
+                        deck.position = position as u32;
+                    }
+                }
+            }
+            AppMessage::AllocateOwned => {
+                let order = self.deck_order();
+                self.allocation = stats::allocate_owned(&mut self.decks, &order, &self.owned);
+            }
+            AppMessage::UpdateTagInput(tag) => self.tag_input = tag,
+            AppMessage::AddCardTag(deck_id, card_name, tag) => {
+                let tag = tag.trim().to_lowercase();
+                if tag.is_empty() {
+                    return iced::Command::none();
+                }
+                if let Some(deck) = self.decks.get_mut(&deck_id) {
+                    deck.cards
+                        .iter_mut()
+                        .filter(|c| c.card.name == card_name && !c.tags.contains(&tag))
+                        .for_each(|c| c.tags.push(tag.clone()));
+                }
+                self.tag_input = String::new();
+            }
+            AppMessage::RemoveCardTag(deck_id, card_name, tag) => {
+                if let Some(deck) = self.decks.get_mut(&deck_id) {
+                    deck.cards
+                        .iter_mut()
+                        .filter(|c| c.card.name == card_name)
+                        .for_each(|c| c.tags.retain(|t| *t != tag));
+                }
+            }
+            AppMessage::UpdateDeckTagInput(tag) => self.deck_tag_input = tag,
+            AppMessage::AddDeckTag(deck_id, tag) => {
+                let tag = tag.trim().to_lowercase();
+                if tag.is_empty() {
+                    return iced::Command::none();
+                }
+                if let Some(deck) = self.decks.get_mut(&deck_id) {
+                    if !deck.tags.contains(&tag) {
+                        deck.tags.push(tag);
+                    }
+                }
+                self.deck_tag_input = String::new();
+            }
+            AppMessage::RemoveDeckTag(deck_id, tag) => {
+                if let Some(deck) = self.decks.get_mut(&deck_id) {
+                    deck.tags.retain(|t| *t != tag);
+                }
+                if !self.decks.values().any(|d| d.tags.contains(&tag)) {
+                    self.deck_tag_filter = self.deck_tag_filter.take().filter(|t| *t != tag);
+                }
+            }
+            AppMessage::FilterDecksByTag(tag) => self.deck_tag_filter = tag,
+            AppMessage::SelectSimulationTarget(name) => self.simulation_target = Some(name),
+            AppMessage::UpdateSimulationTurn(turn) => self.simulation_turn = turn,
+            AppMessage::SimulateHands(deck_id) => {
+                if let Some(deck) = self.decks.get(&deck_id) {
+                    self.simulation = Some(stats::simulate_opening_hands(
+                        &deck.cards,
+                        self.simulation_target.as_deref(),
+                        self.simulation_turn as usize,
+                        stats::SIMULATION_RUNS,
+                    ));
+                }
+            }
+        };
+
+        iced::Command::none()
+    }
+
+    /// Deck ids in the order the user arranged them, which is also the order
+    /// decks are prioritised in.
+    fn deck_order(&self) -> Vec<Uuid> {
+        let mut ids = self.decks.keys().copied().collect::<Vec<_>>();
+        ids.sort_by(|a, b| {
+            let (a, b) = (&self.decks[a], &self.decks[b]);
+            a.position
+                .cmp(&b.position)
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        ids
+    }
+
+    /// Position that puts a new deck at the end of the list.
+    fn next_deck_position(&self) -> u32 {
+        self.decks
+            .values()
+            .map(|d| d.position + 1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Downloads a card image unless it's already cached or on its way.
+    fn fetch_image(&mut self, name: &str, img: &str, fallbacks: &[String]) -> Command<AppMessage> {
+        if self.image_cache.contains_key(name) {
+            self.image_cache.touch(name);
+            return Command::none();
+        }
+        if !self.loading_images.insert(name.to_owned()) {
+            return Command::none();
+        }
+        self.failed_images.remove(name);
+
+        let (name, img, fallbacks) = (name.to_owned(), img.to_owned(), fallbacks.to_vec());
+        let timeout = self.settings.request_timeout();
+        Command::perform(
+            async move { download_image(&name, &img, &fallbacks, timeout).await },
+            |res| AppMessage::UpdateImageCache(res.0, res.1),
+        )
+    }
+
+    /// Makes `url` the first image tried for `card_name` from now on.
+    fn remember_image_url(&mut self, card_name: &str, url: &str) {
+        let cards = self
+            .decks
+            .values_mut()
+            .flat_map(|d| d.cards.iter_mut().chain(d.tokens.iter_mut()))
+            .map(|c| &mut c.card)
+            .filter(|c| c.name == card_name && c.img != url);
+        for card in cards {
+            let previous = std::mem::replace(&mut card.img, url.to_owned());
+            card.img_fallbacks.retain(|u| u != url);
+            card.img_fallbacks.push(previous);
+        }
+
+        let indexed = self
+            .card_index
+            .iter_mut()
+            .filter(|c| c.name == card_name && c.img != url);
+        for card in indexed {
+            let previous = std::mem::replace(&mut card.img, url.to_owned());
+            card.img_fallbacks.retain(|u| u != url);
+            card.img_fallbacks.push(previous);
+        }
+    }
+
+    /// `name`, or `name (2)`, `name (3)`... if another deck already uses it.
+    /// `except` is the deck being renamed, which may keep its own name.
+    fn unique_deck_name(&self, name: &str, except: Option<Uuid>) -> String {
+        let taken = |candidate: &str| {
+            self.decks
+                .iter()
+                .any(|(id, d)| Some(*id) != except && d.name == candidate)
+        };
+        if !taken(name) {
+            return name.to_owned();
+        }
+        (2..)
+            .map(|n| format!("{} ({})", name, n))
+            .find(|candidate| !taken(candidate))
+            .unwrap_or_default()
+    }
+
+    /// Adds decks alongside the existing ones under fresh ids, renaming any
+    /// whose name is already taken. Returns the newly indexed cards.
+    fn merge_decks(&mut self, decks: Vec<Deck>) -> Vec<IndexedCard> {
+        let mut imported = Vec::new();
+
+        for mut deck in decks {
+            deck.name = self.unique_deck_name(&deck.name, None);
+
+            deck.position = self.next_deck_position();
+            let deck_id = Uuid::new_v4();
+            imported.append(&mut build_card_index(deck_id, &deck.cards));
+            self.decks.insert(deck_id, deck);
+        }
+
+        self.card_index.extend(imported.iter().cloned());
+        imported
+    }
+}
+
+/// Shortcuts for keys no focused widget has taken, so typing into a text
+/// field never triggers them.
+fn handle_key_press(key: Key, modifiers: Modifiers) -> Option<AppMessage> {
+    match key.as_ref() {
+        Key::Character("e" | "E") if modifiers.command() => Some(AppMessage::Export),
+        Key::Character("o" | "O") if modifiers.command() => Some(AppMessage::Import),
+        Key::Character(c) if modifiers.is_empty() => {
+            let section = match c {
+                "1" => Section::Decks,
+                "2" => Section::AddDeck,
+                "3" => Section::BuildDecks,
+                "4" => Section::Collection,
+                _ => return None,
+            };
+            Some(AppMessage::ChangeSection(section))
+        }
+        Key::Character("v" | "V") if modifiers.command() && modifiers.shift() => {
+            Some(AppMessage::DeckFromClipboard)
+        }
+        Key::Character("z" | "Z") if modifiers.command() && modifiers.shift() => {
+            Some(AppMessage::Redo)
+        }
+        Key::Character("z" | "Z") if modifiers.command() => Some(AppMessage::Undo),
+        Key::Character("y" | "Y") if modifiers.command() => Some(AppMessage::Redo),
+        Key::Named(Named::F11) => Some(AppMessage::ToggleFocusMode),
+        Key::Named(Named::Escape) => Some(AppMessage::NavigateBack),
+        _ => None,
+    }
+}
+
+fn nav_button<'a>(app: &App, label: &'a str, section: Section) -> AppElement<'a> {
+    let active = match (&app.section, &section) {
+        (Section::ViewDeck(_), Section::Decks) => true,
+        (current, target) => current == target,
+    };
+    let style = if active {
+        theme::Button::Primary
+    } else {
+        theme::Button::Secondary
+    };
+
+    widget::button(label)
+        .width(Length::Fixed(100.))
+        .style(style)
+        .on_press(AppMessage::ChangeSection(section))
+        .into()
+}
+
+fn view_decks(app: &App) -> AppElement<'_> {
+    let btn_export = widget::button("Export").on_press(AppMessage::Export);
+    let btn_import = widget::button("Import").on_press(AppMessage::Import);
+    let btn_import_merge = widget::button("Import and merge").on_press(AppMessage::ImportMerge);
+
+    let btn_from_clipboard =
+        widget::button("New deck from clipboard").on_press(AppMessage::DeckFromClipboard);
+
+    let mut tags = app
+        .decks
+        .values()
+        .flat_map(|d| d.tags.iter().cloned())
+        .collect::<Vec<_>>();
+    tags.sort();
+    tags.dedup();
+    let pick_tag = widget::pick_list(tags, app.deck_tag_filter.clone(), |t| {
+        AppMessage::FilterDecksByTag(Some(t))
+    })
+    .placeholder("Filter by tag");
+    let btn_all_tags = widget::button("All decks").on_press_maybe(
+        app.deck_tag_filter
+            .is_some()
+            .then_some(AppMessage::FilterDecksByTag(None)),
+    );
+
+    let row_buttons = row!(
+        btn_export,
+        btn_import,
+        btn_import_merge,
+        btn_from_clipboard,
+        pick_tag,
+        btn_all_tags
+    );
+
+    let col_decks = widget::column(
+        app.deck_order()
+            .into_iter()
+            .map(|id| (id, &app.decks[&id]))
+            .filter(|(_, d)| {
+                app.deck_tag_filter
+                    .as_ref()
+                    .is_none_or(|tag| d.tags.contains(tag))
+            })
+            .map(|(id, d)| view_deck_general(id, d, app.confirm_delete == Some(id))),
+    );
+
+    column!(row_buttons, col_decks).into()
+}
+
+fn view_error_banner(app: &App) -> AppElement<'_> {
+    let error = match &app.error_banner {
+        Some(e) => e,
+        None => return widget::column([]).into(),
+    };
+
+    widget::container(
+        row!(
+            widget::text(error)
+                .style(Color::from_rgb(0.9, 0.3, 0.3))
+                .width(Length::Fill),
+            widget::button("Dismiss")
+                .style(theme::Button::Text)
+                .on_press(AppMessage::DismissError)
+        )
+        .align_items(iced::Alignment::Center),
+    )
+    .padding(5)
+    .style(theme::Container::Box)
+    .into()
+}
+
+fn view_deck_general(id: Uuid, deck: &Deck, confirm_delete: bool) -> AppElement<'_> {
+    let deck_total_cards = deck.cards.iter().fold(0, |acc, card| acc + card.quantity);
+    let deck_current_cards = deck
+        .cards
+        .iter()
+        .fold(0, |acc, card| acc + card.current_quantity);
+    let mut deck_text = format!(
+        "{} ({}/{} cards",
+        deck.name, deck_current_cards, deck_total_cards
+    );
+    if !deck.sideboard.is_empty() {
+        deck_text.push_str(&format!(
+            ", {} in sideboard",
+            stats::card_count(&deck.sideboard)
+        ));
+    }
+    deck_text.push(')');
+    let completion = if deck_total_cards > 0 {
+        deck_current_cards as f32 / deck_total_cards as f32
+    } else {
+        0.
+    };
+    let bar_completion = widget::progress_bar(0.0..=1.0, completion)
+        .width(Length::Fixed(100.))
+        .height(10);
+    let txt_price = widget::text(format_deck_price(&deck.cards));
+    let txt_name = widget::text(deck_text);
+    let tags = widget::row(deck.tags.iter().map(|tag| {
+        widget::container(widget::text(tag).size(12))
+            .padding([0, 4])
+            .style(theme::Container::Box)
+            .into()
+    }))
+    .spacing(3)
+    .width(Length::Fill);
+    let btn_view = widget::button("View").on_press(AppMessage::ViewDeck(id));
+    let btn_duplicate = widget::button("Duplicate").on_press(AppMessage::DuplicateDeck(id));
+    let btn_up = widget::button("Up").on_press(AppMessage::MoveDeck(id, Direction::Up));
+    let btn_down = widget::button("Down").on_press(AppMessage::MoveDeck(id, Direction::Down));
+    let delete: AppElement = if confirm_delete {
+        row!(
+            widget::text("Delete deck?"),
+            widget::button("Confirm").on_press(AppMessage::DeleteDeck(id)),
+            widget::button("Cancel").on_press(AppMessage::CancelDeleteDeck)
+        )
+        .spacing(5)
+        .align_items(iced::Alignment::Center)
+        .into()
+    } else {
+        widget::button("Delete")
+            .on_press(AppMessage::RequestDeleteDeck(id))
+            .into()
+    };
+
+    row!(
+        column!(btn_up, btn_down),
+        txt_name,
+        bar_completion,
+        tags,
+        view_color_pips(&deck.cards),
+        txt_price,
+        btn_view,
+        btn_duplicate,
+        delete
+    )
+    .spacing(10)
+    .align_items(iced::Alignment::Center)
+    .into()
+}
+
+fn view_add_deck(app: &App) -> AppElement<'_> {
+    let deck_input = widget::text_editor(&app.deck_input_content)
+        .on_action(AppMessage::EditDeckInput)
+        .height(400);
+
+    let btn_analyze = widget::button("Analyze").on_press(AppMessage::AnalyzeDeck);
+    let btn_paste = widget::button("Paste and analyze").on_press(AppMessage::PasteDecklist);
+    let btn_open_file =
+        widget::button("Open decklist file").on_press(AppMessage::ImportDecklistFile);
+    let pick_format = widget::pick_list(GameFormat::ALL, app.analysis_format, |f| {
+        AppMessage::SetAnalysisFormat(Some(f))
+    })
+    .placeholder("Check format");
+    let btn_clear_format = widget::button("Any format").on_press_maybe(
+        app.analysis_format
+            .is_some()
+            .then_some(AppMessage::SetAnalysisFormat(None)),
+    );
+
+    let output = widget::scrollable(widget::text(&app.deck_output));
+
+    let diff = widget::column(app.deck_diff.iter().map(|d| {
+        let (line, color) = match d {
+            CardDiff::Added(name, q) => {
+                (format!("+ {} {}", q, name), Color::from_rgb(0.3, 0.8, 0.3))
+            }
+            CardDiff::Removed(name, q) => {
+                (format!("- {} {}", q, name), Color::from_rgb(0.9, 0.3, 0.3))
+            }
+            CardDiff::Changed(name, old, new) => (
+                format!("~ {} {} -> {}", name, old, new),
+                Color::from_rgb(0.9, 0.8, 0.3),
+            ),
+        };
+        widget::text(line).style(color).into()
+    }));
+
+    let mut column = column!(
+        deck_input,
+        row!(
+            btn_analyze,
+            btn_paste,
+            btn_open_file,
+            pick_format,
+            btn_clear_format
+        )
+        .spacing(5),
+        output,
+        diff
+    );
+
+    if app.deck_in_progress.is_some() {
+        let field_deck_name =
+            widget::text_input("Deck name", &app.deck_name).on_input(AppMessage::UpdateDeckName);
+        let label = if app.editing_deck.is_some() {
+            "Save deck"
+        } else {
+            "Create deck"
+        };
+        let btn_create_deck = widget::button(label)
+            .on_press_maybe((!app.deck_name.trim().is_empty()).then_some(AppMessage::CreateDeck));
+
+        column = column.push(field_deck_name);
+        let name = app.deck_name.trim();
+        let unique_name = app.unique_deck_name(name, app.editing_deck);
+        if unique_name != name {
+            column = column.push(
+                widget::text(format!(
+                    "A deck named {} already exists, this one will be saved as {}",
+                    name, unique_name
+                ))
+                .style(Color::from_rgb(0.9, 0.8, 0.3)),
+            );
+        }
+        let btn_cancel_edit = app
+            .editing_deck
+            .map(|_| widget::button("Cancel edit").on_press(AppMessage::CancelEdit));
+        column = column.push(row!(btn_create_deck).push_maybe(btn_cancel_edit).spacing(5));
+    }
+
+    column.into()
+}
+
+fn view_deck_builder(app: &App) -> AppElement<'_> {
+    let search_box =
+        widget::text_input("search card...", &app.search_text).on_input(AppMessage::Search);
+    let scopes = std::iter::once(SearchScope::AllDecks)
+        .chain(
+            app.deck_order()
+                .into_iter()
+                .map(|id| SearchScope::Deck(id, app.decks[&id].name.clone())),
+        )
+        .collect::<Vec<_>>();
+    let pick_scope = widget::pick_list(
+        scopes,
+        Some(app.search_scope.clone()),
+        AppMessage::SetSearchScope,
+    );
+
+    let txt_count = widget::text(format!(
+        "Showing {} of {} matches",
+        app.search_result.len(),
+        app.search_match_count
+    ));
+    let btn_more = widget::button("Show more").on_press_maybe(
+        (app.search_match_count > app.search_result.len()).then_some(AppMessage::ShowMoreResults),
+    );
+
+    let card_results = widget::scrollable(widget::column(
+        app.search_result.iter().map(|c| view_card_result(app, c)),
+    ))
+    .width(Length::Fill);
+
+    column!(
+        row!(search_box, pick_scope).spacing(5),
+        row!(txt_count, btn_more)
+            .spacing(10)
+            .align_items(iced::Alignment::Center),
+        card_results
+    )
+    .into()
+}
+
+fn view_card_result<'a>(app: &'a App, card: &'a IndexedCard) -> AppElement<'a> {
+    let img = card_image(app, &card.name, app.settings.thumbnail_height);
+
+    // Results can briefly outlive their deck; skip them until the next search.
+    let deck = match app.decks.get(&card.deck_id) {
+        Some(d) => d,
+        None => return widget::text("").into(),
+    };
+    let card_in_deck = deck.cards.iter().find(|&c| c.card.name == card.name);
+    let card_totals: AppElement = match card_in_deck {
+        Some(c) => quantity_input(card.deck_id, c),
+        None => widget::text("").into(),
+    };
+    let card_info = row!(
+        card_name_label(&card.name),
+        widget::text(format!(" ({})", deck.name))
+    );
+
+    let should_allow_add = if card_in_deck.map(|c| c.current_quantity).unwrap_or(0)
+        < card_in_deck.map(|c| c.quantity).unwrap_or(0)
+    {
+        Some(AppMessage::AddCard(card.deck_id, card.name.clone()))
+    } else {
+        None
+    };
+    let should_allow_remove = if card_in_deck.map(|c| c.current_quantity).unwrap_or(0) > 0 {
+        Some(AppMessage::RemoveCard(card.deck_id, card.name.clone()))
+    } else {
+        None
+    };
+
+    let btn_add_card = widget::button("Add card").on_press_maybe(should_allow_add);
+    let btn_remove_card = widget::button("Remove card").on_press_maybe(should_allow_remove);
+
+    let card_col = column!(card_info, card_totals, row!(btn_add_card, btn_remove_card));
+
+    row!(img, card_col).into()
+}
+
+/// Owned copies as an editable field next to the copies the deck needs.
+fn quantity_input<'a>(deck_id: Uuid, card_in_deck: &CardInDeck) -> AppElement<'a> {
+    let card_name = card_in_deck.card.name.clone();
+    let current = card_in_deck.current_quantity;
+    let input = widget::text_input("0", &current.to_string())
+        .on_input(move |value| {
+            let quantity = match value.trim() {
+                "" => 0,
+                v => v.parse().unwrap_or(current),
+            };
+            AppMessage::SetCardQuantity(deck_id, card_name.clone(), quantity)
+        })
+        .width(Length::Fixed(50.));
+
+    row!(input, widget::text(format!("/{}", card_in_deck.quantity)))
+        .align_items(iced::Alignment::Center)
+        .into()
+}
+
+fn view_deck<'a>(app: &'a App, deck_id: Uuid) -> AppElement<'a> {
+    let deck = match app.decks.get(&deck_id) {
+        Some(d) => d,
+        None => return widget::text("oopsies").into(),
+    };
+
+    let input_title = widget::text_input("Deck name", &app.rename_input)
+        .on_input(AppMessage::UpdateRenameInput)
+        .on_submit(AppMessage::RenameDeck(deck_id, app.rename_input.clone()))
+        .width(Length::Fill);
+    let btn_export = widget::button("Export deck").on_press(AppMessage::ExportDeck(deck_id, true));
+    let btn_share =
+        widget::button("Export without progress").on_press(AppMessage::ExportDeck(deck_id, false));
+    let btn_import_progress =
+        widget::button("Import progress").on_press(AppMessage::ImportProgress(deck_id));
+    let btn_edit = widget::button("Edit deck").on_press(AppMessage::EditDeck(deck_id));
+    let btn_export_decklist =
+        widget::button("Export decklist").on_press(AppMessage::ExportDecklist(deck_id));
+    let btn_proxies =
+        widget::button("Print proxies").on_press(AppMessage::ExportProxySheet(deck_id));
+    let btn_reload_images =
+        widget::button("Reload images").on_press(AppMessage::ReloadDeckImages(deck_id));
+    let pick_format = widget::pick_list(GameFormat::ALL, deck.format, move |f| {
+        AppMessage::SetDeckFormat(deck_id, Some(f))
+    })
+    .placeholder("Format");
+    let row_title = row!(
+        input_title,
+        pick_format,
+        btn_edit,
+        btn_export,
+        btn_share,
+        btn_export_decklist,
+        btn_proxies,
+        btn_reload_images,
+        btn_import_progress
+    );
+
+    let deck_tags = widget::row(deck.tags.iter().map(|tag| {
+        widget::button(widget::text(format!("{} x", tag)))
+            .style(theme::Button::Secondary)
+            .on_press(AppMessage::RemoveDeckTag(deck_id, tag.clone()))
+            .into()
+    }))
+    .spacing(5);
+    let deck_tag_input = widget::text_input("new deck tag", &app.deck_tag_input)
+        .on_input(AppMessage::UpdateDeckTagInput)
+        .on_submit(AppMessage::AddDeckTag(deck_id, app.deck_tag_input.clone()))
+        .width(Length::Fixed(150.));
+
+    let filter_box =
+        widget::text_input("find in deck...", &app.deck_filter).on_input(AppMessage::FilterDeck);
+
+    let pick_sort = widget::pick_list(SortMode::ALL, Some(app.deck_sort), AppMessage::SortDeck);
+
+    let chk_missing = widget::checkbox("Missing cards only", app.deck_missing_only)
+        .on_toggle(AppMessage::ToggleDeckMissingOnly);
+    let total_missing = deck
+        .cards
+        .iter()
+        .fold(0, |acc, c| acc + (c.quantity - c.current_quantity).max(0));
+    let txt_missing = widget::text(format!("{} cards missing", total_missing));
+
+    let matcher = SkimMatcherV2::default();
+    let mut shown = deck
+        .cards
+        .iter()
+        .filter(|c| {
+            app.deck_filter.is_empty()
+                || matcher
+                    .fuzzy_match(&c.card.name, &app.deck_filter)
+                    .is_some()
+        })
+        .filter(|c| !app.deck_missing_only || c.current_quantity < c.quantity)
+        .collect::<Vec<_>>();
+    stats::sort_cards(&mut shown, app.deck_sort);
+    if app.deck_missing_only {
+        shown.sort_by_key(|c| std::cmp::Reverse(c.quantity - c.current_quantity));
+    }
+
+    let cards = widget::scrollable(
+        widget::column(
+            shown
+                .into_iter()
+                .map(|c| view_card_in_deck(app, deck_id, c)),
+        )
+        .push(view_deck_sideboard(app, deck))
+        .push(view_deck_tokens(app, deck_id, deck)),
+    )
+    .width(Length::Fill);
+    let cards = row!(cards, view_zoomed_card(app)).spacing(10);
+
+    column!(
+        row_title,
+        row!(
+            view_color_pips(&deck.cards),
+            widget::text(format!("Deck price: {}", format_deck_price(&deck.cards)))
+        )
+        .spacing(10),
+        row!(deck_tags, deck_tag_input)
+            .spacing(5)
+            .align_items(iced::Alignment::Center),
+        view_legality_banner(deck),
+        view_staple_suggestions(deck_id, deck),
+        view_deck_stats(deck),
+        view_mana_curve(deck),
+        view_hand_simulation(app, deck_id, deck),
+        row!(filter_box, pick_sort, chk_missing, txt_missing)
+            .spacing(10)
+            .align_items(iced::Alignment::Center),
+        view_add_new_card(app, deck_id),
+        cards
+    )
+    .into()
+}
+
+/// Large image of the card last clicked, with a button to close it.
+fn view_zoomed_card(app: &App) -> AppElement<'_> {
+    let name = match &app.zoomed_card {
+        Some(n) => n,
+        None => return widget::column([]).into(),
+    };
+
+    let img: AppElement = match app.large_images.get(name) {
+        Some(None) => card_image(app, name, ZOOMED_CARD_HEIGHT),
+        Some(Some(bytes)) => widget::image::<Handle>(Handle::from_memory(bytes.clone()))
+            .content_fit(iced::ContentFit::ScaleDown)
+            .height(ZOOMED_CARD_HEIGHT)
+            .into(),
+        None => widget::container(widget::text("Loading..."))
+            .width(Length::Fixed(ZOOMED_CARD_HEIGHT as f32 * CARD_ASPECT_RATIO))
+            .height(ZOOMED_CARD_HEIGHT)
+            .center_x()
+            .center_y()
+            .style(theme::Container::Box)
+            .into(),
+    };
+
+    column!(
+        widget::button("Close").on_press(AppMessage::HideCardDetail),
+        img
+    )
+    .spacing(5)
+    .into()
+}
+
+fn view_deck_sideboard<'a>(app: &'a App, deck: &'a Deck) -> AppElement<'a> {
+    if deck.sideboard.is_empty() {
+        return widget::column([]).into();
+    }
+
+    let cards = deck.sideboard.iter().map(|c| {
+        let img = card_image(app, &c.card.name, app.settings.thumbnail_height);
+
+        row!(
+            img,
+            column!(
+                card_name_label(&c.card.name),
+                widget::text(format!("x{}", c.quantity))
+            )
+        )
+        .into()
+    });
+
+    column!(widget::text(format!(
+        "Sideboard ({} cards)",
+        stats::card_count(&deck.sideboard)
+    ))
+    .size(20))
+    .extend(cards)
+    .into()
+}
+
+fn view_add_new_card(app: &App, deck_id: Uuid) -> AppElement<'_> {
+    let input_name = widget::text_input("add a card to this deck", &app.new_card_input)
+        .on_input(AppMessage::UpdateNewCardInput)
+        .on_submit(AppMessage::AddNewCardToDeck(
+            deck_id,
+            app.new_card_input.clone(),
+        ));
+    let current = app.new_card_quantity;
+    let input_quantity = widget::text_input("1", &current.to_string())
+        .on_input(move |value| {
+            let quantity = match value.trim() {
+                "" => 0,
+                v => v.parse().unwrap_or(current),
+            };
+            AppMessage::UpdateNewCardQuantity(quantity)
+        })
+        .width(Length::Fixed(50.));
+    let btn_add = widget::button("Add to deck").on_press(AppMessage::AddNewCardToDeck(
+        deck_id,
+        app.new_card_input.clone(),
+    ));
+
+    row!(
+        input_name,
+        input_quantity,
+        btn_add,
+        widget::text(&app.new_card_output)
+    )
+    .spacing(5)
+    .align_items(iced::Alignment::Center)
+    .into()
+}
+
+fn view_deck_tokens<'a>(app: &'a App, deck_id: Uuid, deck: &'a Deck) -> AppElement<'a> {
+    let input_token = widget::text_input("token or emblem name", &app.token_input)
+        .on_input(AppMessage::UpdateTokenInput)
+        .on_submit(AppMessage::AddToken(deck_id));
+    let btn_add_token = widget::button("Add token").on_press(AppMessage::AddToken(deck_id));
+
+    let tokens = deck.tokens.iter().map(|t| {
+        let img = card_image(app, &t.card.name, app.settings.thumbnail_height);
+        let btn_more = widget::button("+").on_press(AppMessage::ChangeTokenQuantity(
+            deck_id,
+            t.card.name.clone(),
+            1,
+        ));
+        let btn_less = widget::button("-").on_press(AppMessage::ChangeTokenQuantity(
+            deck_id,
+            t.card.name.clone(),
+            -1,
+        ));
+
+        row!(
+            img,
+            column!(
+                card_name_label(&t.card.name),
+                widget::text(format!("x{}", t.quantity)),
+                row!(btn_more, btn_less)
+            )
+        )
+        .into()
+    });
+
+    column!(
+        widget::text("Tokens").size(20),
+        row!(input_token, btn_add_token),
+        widget::text(&app.token_output)
+    )
+    .extend(tokens)
+    .into()
+}
+
+fn view_staple_suggestions(deck_id: Uuid, deck: &Deck) -> AppElement<'_> {
+    let format = match deck.format {
+        Some(f) => f,
+        None => return widget::column([]).into(),
+    };
+    let staples = stats::missing_staples(deck);
+    if staples.is_empty() {
+        return widget::column([]).into();
+    }
+
+    let buttons = staples.into_iter().map(|name| {
+        widget::button(name)
+            .style(theme::Button::Secondary)
+            .on_press(AppMessage::AddStaple(deck_id, name.to_owned()))
+            .into()
+    });
+
+    column!(
+        widget::text(format!(
+            "{} decks often run these, which you don't have (click to add):",
+            format
+        )),
+        widget::row(buttons).spacing(5)
+    )
+    .into()
+}
+
+fn view_legality_banner(deck: &Deck) -> AppElement<'_> {
+    let format = match deck.format {
+        Some(f) => f,
+        None => return widget::column([]).into(),
+    };
+
+    let flagged = stats::flagged_cards(&deck.cards, format);
+    let banned = flagged
+        .iter()
+        .filter(|(_, l)| *l == Legality::Banned)
+        .map(|(c, _)| c.card.name.as_str())
+        .collect::<Vec<_>>();
+    let not_legal = flagged
+        .iter()
+        .filter(|(_, l)| *l == Legality::NotLegal)
+        .map(|(c, _)| c.card.name.as_str())
+        .collect::<Vec<_>>();
+    let restricted = flagged
+        .iter()
+        .filter(|(_, l)| *l == Legality::Restricted)
+        .map(|(c, _)| format!("{} ({})", c.card.name, c.quantity))
+        .collect::<Vec<_>>();
+
+    let mut banner = widget::column([]);
+    if !banned.is_empty() {
+        banner = banner.push(
+            widget::text(format!("Banned in {}: {}", format, banned.join(", ")))
+                .style(Color::from_rgb(0.9, 0.3, 0.3)),
+        );
+    }
+    if !not_legal.is_empty() {
+        banner = banner.push(
+            widget::text(format!("Not legal in {}: {}", format, not_legal.join(", ")))
+                .style(Color::from_rgb(0.9, 0.3, 0.3)),
+        );
+    }
+    if !restricted.is_empty() {
+        banner = banner.push(
+            widget::text(format!(
+                "Restricted in {} (max 1 copy): {}",
+                format,
+                restricted.join(", ")
+            ))
+            .style(Color::from_rgb(0.9, 0.8, 0.3)),
+        );
+    }
+
+    widget::container(banner).padding(5).into()
+}
+
+fn view_color_pips<'a>(cards: &[CardInDeck]) -> AppElement<'a> {
+    let pips = stats::deck_colors(cards).into_iter().map(|color| {
+        let rgb = match color {
+            "W" => Color::from_rgb(0.95, 0.92, 0.75),
+            "U" => Color::from_rgb(0.4, 0.6, 0.95),
+            "B" => Color::from_rgb(0.6, 0.55, 0.6),
+            "R" => Color::from_rgb(0.95, 0.4, 0.3),
+            "G" => Color::from_rgb(0.35, 0.75, 0.4),
+            _ => Color::from_rgb(0.7, 0.7, 0.7),
+        };
+        widget::text(color).style(rgb).into()
+    });
+
+    widget::row(pips).spacing(4).into()
+}
+
+fn format_deck_price(cards: &[CardInDeck]) -> String {
+    let price = stats::deck_price(cards);
+    match price.unpriced {
+        0 => format!("${:.2}", price.total),
+        1 => format!("${:.2} (1 card unpriced)", price.total),
+        n => format!("${:.2} ({} cards unpriced)", price.total, n),
+    }
+}
+
+const MANA_CURVE_HEIGHT: f32 = 80.;
+
+fn view_mana_curve(deck: &Deck) -> AppElement<'_> {
+    let curve = stats::mana_curve(&deck.cards);
+    let max = curve.iter().copied().max().unwrap_or(0).max(1);
+
+    let bars = curve
+        .iter()
+        .zip(stats::MANA_CURVE_LABELS)
+        .map(|(&count, label)| {
+            let bar = widget::container(widget::Space::new(
+                Length::Fixed(24.),
+                Length::Fixed(MANA_CURVE_HEIGHT * count as f32 / max as f32),
+            ))
+            .style(theme::Container::Box);
+
+            column!(
+                widget::Space::with_height(Length::Fill),
+                widget::text(count),
+                bar,
+                widget::text(label)
+            )
+            .align_items(iced::Alignment::Center)
+            .height(Length::Fixed(MANA_CURVE_HEIGHT + 50.))
+            .into()
+        });
+
+    widget::row(bars).spacing(8).into()
+}
+
+fn view_deck_stats(deck: &Deck) -> AppElement<'_> {
+    let lands = stats::land_count(&deck.cards);
+    let line = match stats::recommended_lands(stats::card_count(&deck.cards), deck.format) {
+        Some(advice) => {
+            let mut line = format!(
+                "You have {} lands; {} typically runs {}-{}.",
+                lands, advice.format_name, advice.min, advice.max
+            );
+            if lands < advice.min {
+                line.push_str(&format!(" Consider adding {} more.", advice.min - lands));
+            } else if lands > advice.max {
+                line.push_str(&format!(" Consider cutting {}.", lands - advice.max));
+            }
+            line
+        }
+        None => format!("You have {} lands.", lands),
+    };
+
+    let line = match stats::goldfish_turn(&deck.cards) {
+        Some(turn) => format!("{} Average castable turn: {:.1}.", line, turn),
+        None => line,
+    };
+
+    let types = stats::type_breakdown(&deck.cards)
+        .iter()
+        .map(|(card_type, count)| format!("{} {}", card_type, count))
+        .collect::<Vec<_>>();
+    let nonlands = stats::card_count(&deck.cards) - lands;
+    let txt_types = widget::text(format!("Nonland {}. Types: {}", nonlands, types.join(", ")));
+
+    let roles = stats::tag_counts(&deck.cards)
+        .iter()
+        .map(|(tag, count)| format!("{} {}", tag, count))
+        .collect::<Vec<_>>();
+
+    if roles.is_empty() {
+        column!(widget::text(line), txt_types).into()
+    } else {
+        column!(
+            widget::text(line),
+            txt_types,
+            widget::text(format!("Roles: {}", roles.join(", ")))
+        )
+        .into()
+    }
+}
+
+fn view_hand_simulation<'a>(app: &'a App, deck_id: Uuid, deck: &'a Deck) -> AppElement<'a> {
+    let card_names = deck
+        .cards
+        .iter()
+        .map(|c| c.card.name.clone())
+        .collect::<Vec<_>>();
+    let pick_target = widget::pick_list(
+        card_names,
+        app.simulation_target.clone(),
+        AppMessage::SelectSimulationTarget,
+    )
+    .placeholder("Target card");
+    let slider_turn = widget::slider(
+        1..=10,
+        app.simulation_turn,
+        AppMessage::UpdateSimulationTurn,
+    )
+    .width(Length::Fixed(150.));
+    let txt_turn = widget::text(format!("by turn {}", app.simulation_turn));
+    let btn_simulate =
+        widget::button("Simulate hands").on_press(AppMessage::SimulateHands(deck_id));
+
+    let controls = row!(pick_target, txt_turn, slider_turn, btn_simulate).spacing(10);
+
+    let results = match &app.simulation {
+        Some(sim) => {
+            let mut lines = format!(
+                "{} opening hands from {} cards:\n\
+                 2-4 lands: {:.1}%, 0-1 lands: {:.1}%, 5+ lands: {:.1}%",
+                sim.runs,
+                sim.deck_size,
+                sim.lands_2_to_4 * 100.,
+                sim.lands_0_to_1 * 100.,
+                sim.lands_5_plus * 100.,
+            );
+            if let Some(target) = &sim.target {
+                lines.push_str(&format!(
+                    "\n{} by turn {}: {:.1}%",
+                    target.card_name,
+                    target.turn,
+                    target.probability * 100.
+                ));
+            }
+            widget::text(lines)
+        }
+        None => widget::text(""),
+    };
+
+    column!(controls, results).into()
+}
+
+fn view_card_in_deck<'a>(
+    app: &'a App,
+    deck_id: Uuid,
+    card_in_deck: &'a CardInDeck,
+) -> AppElement<'a> {
+    let img = widget::button(card_image(
+        app,
+        &card_in_deck.card.name,
+        app.settings.thumbnail_height,
+    ))
+    .style(theme::Button::Text)
+    .padding(0)
+    .on_press(AppMessage::ShowCardDetail(card_in_deck.card.name.clone()));
+
+    let card_info = widget::button(card_name_label(&card_in_deck.card.name))
+        .style(theme::Button::Text)
+        .padding(0)
+        .on_press(AppMessage::ViewCard(
+            deck_id,
+            card_in_deck.card.name.clone(),
+        ));
+    let shortfall = card_in_deck.quantity - card_in_deck.current_quantity;
+    let card_totals = if app.deck_missing_only && shortfall > 0 {
+        row!(
+            quantity_input(deck_id, card_in_deck),
+            widget::text(format!("  {} missing", shortfall))
+        )
+        .into()
+    } else {
+        quantity_input(deck_id, card_in_deck)
+    };
+
+    let should_allow_add = if card_in_deck.current_quantity < card_in_deck.quantity {
+        Some(AppMessage::AddCard(deck_id, card_in_deck.card.name.clone()))
+    } else {
+        None
+    };
+    let should_allow_remove = if card_in_deck.current_quantity > 0 {
+        Some(AppMessage::RemoveCard(
+            deck_id,
+            card_in_deck.card.name.clone(),
+        ))
+    } else {
+        None
+    };
+
+    let btn_add_card = widget::button("Add card").on_press_maybe(should_allow_add);
+    let btn_remove_card = widget::button("Remove card").on_press_maybe(should_allow_remove);
+
+    // One condition per collected copy, so copies in different shape are
+    // told apart.
+    let pick_condition = |copy: usize| -> AppElement<'a> {
+        let card_name = card_in_deck.card.name.clone();
+        widget::pick_list(
+            CardCondition::ALL,
+            card_in_deck.copy_condition(copy),
+            move |condition| {
+                AppMessage::SetCardCondition(deck_id, card_name.clone(), copy, condition)
+            },
+        )
+        .placeholder(format!("Copy {}", copy + 1))
+        .into()
+    };
+    let copies = (0..card_in_deck.current_quantity.max(0) as usize).collect::<Vec<_>>();
+    let conditions = widget::column(copies.chunks(CONDITIONS_PER_ROW).map(|chunk| {
+        widget::row(chunk.iter().map(|&copy| pick_condition(copy)))
+            .spacing(5)
+            .into()
+    }))
+    .spacing(5);
+    let btn_clear_condition = widget::button("Clear conditions").on_press_maybe(
+        card_in_deck
+            .conditions
+            .iter()
+            .any(Option::is_some)
+            .then(|| AppMessage::ClearCardConditions(deck_id, card_in_deck.card.name.clone())),
+    );
+
+    let card_name = card_in_deck.card.name.clone();
+    let input_note = widget::text_input("note", card_in_deck.note.as_deref().unwrap_or_default())
+        .on_input(move |note| AppMessage::SetCardNote(deck_id, card_name.clone(), note))
+        .width(Length::Fixed(200.));
+
+    let card_col = column!(
+        card_info,
+        card_totals,
+        row!(btn_add_card, btn_remove_card),
+        row!(conditions, btn_clear_condition).spacing(5),
+        input_note
+    );
+    row!(img, card_col).into()
+}
+
+fn view_card_table(app: &App) -> AppElement<'_> {
+    let filter_box = widget::text_input("filter cards...", &app.card_table_filter)
+        .on_input(AppMessage::FilterCardTable);
+
+    let filter = app.card_table_filter.to_lowercase();
+    let all_cards = stats::aggregate_collection(&app.decks, &app.card_index);
+    let collection_value = stats::collection_value(&all_cards);
+    let mut cards = all_cards
+        .into_iter()
+        .filter(|c| c.name.to_lowercase().contains(&filter))
+        .collect::<Vec<_>>();
+    sort_collection(&mut cards, app.card_table_sort, app.card_table_ascending);
+
+    let header_button = |label: &str, column: CardTableColumn| {
+        let label = if app.card_table_sort == column {
+            let arrow = if app.card_table_ascending { "^" } else { "v" };
+            format!("{} {}", label, arrow)
+        } else {
+            label.to_owned()
         };
+        widget::button(widget::text(label))
+            .style(theme::Button::Text)
+            .on_press(AppMessage::SortCardTable(column))
+    };
+
+    let header = row!(
+        header_button("Name", CardTableColumn::Name).width(Length::FillPortion(4)),
+        header_button("Needed", CardTableColumn::Needed).width(Length::FillPortion(1)),
+        header_button("Owned", CardTableColumn::Owned).width(Length::FillPortion(1)),
+        header_button("Decks", CardTableColumn::Decks).width(Length::FillPortion(1)),
+        widget::text("Collection").width(Length::FillPortion(1)),
+        header_button("Price", CardTableColumn::Price).width(Length::FillPortion(1)),
+        header_button("Set", CardTableColumn::Set).width(Length::FillPortion(1)),
+    );
+
+    let rows = widget::scrollable(widget::column(cards.into_iter().map(|c| {
+        let btn_name = widget::button(card_name_label(&c.name))
+            .style(theme::Button::Text)
+            .width(Length::FillPortion(4))
+            .on_press(AppMessage::ViewCard(c.deck_ids[0], c.name.clone()));
+        let price = c.price.map(|p| format!("${:.2}", p)).unwrap_or_default();
+
+        row!(
+            btn_name,
+            widget::text(c.needed).width(Length::FillPortion(1)),
+            widget::text(c.owned).width(Length::FillPortion(1)),
+            widget::text(c.deck_ids.len()).width(Length::FillPortion(1)),
+            widget::text_input(
+                "-",
+                &app.owned
+                    .get(&c.name)
+                    .map(|o| o.to_string())
+                    .unwrap_or_default()
+            )
+            .on_input(move |v| AppMessage::SetOwned(c.name.clone(), v))
+            .width(Length::FillPortion(1)),
+            widget::text(price).width(Length::FillPortion(1)),
+            widget::text(c.set.unwrap_or_default().to_uppercase()).width(Length::FillPortion(1)),
+        )
+        .into()
+    })))
+    .width(Length::Fill);
+
+    column!(
+        view_price_history(app, collection_value),
+        view_allocation(app),
+        filter_box,
+        header,
+        rows
+    )
+    .into()
+}
+
+fn view_allocation(app: &App) -> AppElement<'_> {
+    let btn_allocate = widget::button("Fill decks from collection")
+        .on_press_maybe((!app.owned.is_empty()).then_some(AppMessage::AllocateOwned));
+
+    let summary = app
+        .allocation
+        .iter()
+        .map(|a| format!("{}: {}/{}", a.deck_name, a.allocated, a.needed))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    row!(btn_allocate, widget::text(summary)).spacing(10).into()
+}
+
+fn view_price_history(app: &App, collection_value: f32) -> AppElement<'_> {
+    let txt_value = widget::text(format!("Collection value: ${:.2}", collection_value));
+    let btn_record = widget::button("Record snapshot").on_press(AppMessage::RecordPriceSnapshot);
+    let btn_refresh = widget::button("Refresh prices")
+        .on_press_maybe((!app.refreshing_prices).then_some(AppMessage::RefreshPrices));
+    let txt_updated = widget::text(match app.prices_updated_at {
+        _ if app.refreshing_prices => "Refreshing prices...".to_owned(),
+        Some(ts) => chrono::DateTime::from_timestamp(ts, 0)
+            .map(|d| format!("Prices updated {}", d.format("%Y-%m-%d %H:%M")))
+            .unwrap_or_default(),
+        None => "Prices not refreshed yet".to_owned(),
+    });
+
+    let history = match (app.price_history.first(), app.price_history.last()) {
+        (Some(first), Some(last)) => {
+            let values = app
+                .price_history
+                .iter()
+                .map(|s| s.total_value)
+                .collect::<Vec<_>>();
+            let date = |ts: i64| {
+                chrono::DateTime::from_timestamp(ts, 0)
+                    .map(|d| d.format("%Y-%m-%d").to_string())
+                    .unwrap_or_default()
+            };
+            format!(
+                "{} ${:.2} {} {} ${:.2}",
+                date(first.timestamp),
+                first.total_value,
+                stats::sparkline(&values),
+                date(last.timestamp),
+                last.total_value
+            )
+        }
+        _ => "No snapshots recorded yet.".to_owned(),
+    };
+
+    column!(
+        row!(txt_value, btn_record, btn_refresh, txt_updated).spacing(10),
+        widget::text(history)
+    )
+    .into()
+}
+
+const COLLECTION_COLUMNS: usize = 6;
+
+fn view_collection(app: &App) -> AppElement<'_> {
+    let mut cards = stats::aggregate_collection(&app.decks, &app.card_index);
+    cards.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let rows = cards.chunks(COLLECTION_COLUMNS).map(|chunk| {
+        widget::row(chunk.iter().map(|c| {
+            column!(
+                card_image(app, &c.name, app.settings.thumbnail_height),
+                card_name_label(&c.name),
+                widget::text(format!("{}/{}", c.owned, c.needed))
+            )
+            .width(Length::FillPortion(1))
+            .into()
+        }))
+        .spacing(10)
+        .into()
+    });
+
+    let btn_export =
+        widget::button("Export collection CSV").on_press(AppMessage::ExportCollectionCsv);
+
+    column!(
+        btn_export,
+        widget::scrollable(widget::column(rows).spacing(10)).width(Length::Fill)
+    )
+    .spacing(10)
+    .into()
+}
+
+fn view_shopping_list(app: &App) -> AppElement<'_> {
+    let pick_type = widget::pick_list(CardType::ALL, app.shopping_type_filter, |t| {
+        AppMessage::FilterShoppingList(Some(t))
+    })
+    .placeholder("All types");
+    let btn_all_types = widget::button("All types").on_press_maybe(
+        app.shopping_type_filter
+            .map(|_| AppMessage::FilterShoppingList(None)),
+    );
+
+    let mut missing = stats::aggregate_collection(&app.decks, &app.card_index)
+        .into_iter()
+        .filter(|c| c.missing() > 0)
+        .filter(|c| match app.shopping_type_filter {
+            Some(t) => t.matches(c.type_line.as_deref()),
+            None => true,
+        })
+        .collect::<Vec<_>>();
+    missing.sort_by(|a, b| {
+        b.missing()
+            .cmp(&a.missing())
+            .then_with(|| a.name.cmp(&b.name))
+    });
+
+    let total_missing = missing.iter().fold(0, |acc, c| acc + c.missing());
+    let total_price = missing.iter().fold(0., |acc, c| {
+        acc + c.price.unwrap_or(0.) * c.missing() as f32
+    });
+    let txt_total = widget::text(format!(
+        "{} cards missing (${:.2})",
+        total_missing, total_price
+    ));
 
-        row!(list_btn, content).into()
-    }
+    let rows = widget::scrollable(widget::column(missing.into_iter().map(|c| {
+        let price = c.price.map(|p| format!("${:.2}", p)).unwrap_or_default();
+        row!(
+            widget::text(c.missing()).width(Length::Fixed(40.)),
+            widget::text(&c.name).width(Length::Fill),
+            widget::text(c.type_line.unwrap_or_default()).width(Length::Fill),
+            widget::text(price).width(Length::Fixed(80.)),
+        )
+        .into()
+    })))
+    .width(Length::Fill);
+
+    column!(row!(pick_type, btn_all_types).spacing(5), txt_total, rows).into()
 }
 
-fn view_decks(app: &App) -> AppElement {
-    let btn_export = widget::button("Export").on_press(AppMessage::Export);
-    let btn_import = widget::button("Import").on_press(AppMessage::Import);
+const SHORTFALL_PREVIEW: usize = 5;
+/// Copy condition pickers shown side by side in the deck view.
+const CONDITIONS_PER_ROW: usize = 4;
+
+fn view_buildable(app: &App) -> AppElement<'_> {
+    if app.owned.is_empty() {
+        return widget::text(
+            "Enter owned counts in the Cards section to see which decks you can build.",
+        )
+        .into();
+    }
 
-    let row_buttons = row!(btn_export, btn_import);
+    let decks = stats::buildable_decks(&app.decks, &app.owned);
 
-    let col_decks = widget::column(app.decks.iter().map(|(k, v)| view_deck_general(k, v)));
+    let rows = widget::scrollable(widget::column(decks.into_iter().map(|b| {
+        let btn_name = widget::button(widget::text(format!(
+            "{} - {:.0}% ({}/{})",
+            b.deck_name,
+            b.completion() * 100.,
+            b.available,
+            b.needed
+        )))
+        .style(theme::Button::Text)
+        .on_press(AppMessage::ViewDeck(b.deck_id));
 
-    column!(row_buttons, col_decks).into()
+        let shortfall = if b.shortfall.is_empty() {
+            "Ready to play".to_owned()
+        } else {
+            let mut missing = b
+                .shortfall
+                .iter()
+                .take(SHORTFALL_PREVIEW)
+                .map(|(name, q)| format!("{} {}", q, name))
+                .collect::<Vec<_>>();
+            if b.shortfall.len() > SHORTFALL_PREVIEW {
+                missing.push(format!(
+                    "and {} more",
+                    b.shortfall.len() - SHORTFALL_PREVIEW
+                ));
+            }
+            format!("Missing: {}", missing.join(", "))
+        };
+
+        column!(btn_name, widget::text(shortfall)).into()
+    })))
+    .width(Length::Fill);
+
+    rows.into()
 }
 
-fn view_deck_general<'a>(id: &'a Uuid, deck: &'a Deck) -> AppElement<'a> {
-    let deck_total_cards = deck.cards.iter().fold(0, |acc, card| acc + card.quantity);
-    let deck_current_cards = deck
-        .cards
-        .iter()
-        .fold(0, |acc, card| acc + card.current_quantity);
-    let deck_text = format!(
-        "{} ({}/{} cards)",
-        deck.name, deck_current_cards, deck_total_cards
-    );
-    let txt_name = widget::text(deck_text).width(Length::Fill);
-    let btn_view = widget::button("View").on_press(AppMessage::ViewDeck(id.clone()));
-    let btn_delete = widget::button("Delete").on_press(AppMessage::DeleteDeck(id.clone()));
+fn view_overcommitted(app: &App) -> AppElement<'_> {
+    if app.owned.is_empty() {
+        return widget::text(
+            "Enter owned counts in the Cards section to see which cards your decks share.",
+        )
+        .into();
+    }
+
+    let cards = stats::overcommitted_cards(&app.decks, &app.owned);
+    if cards.is_empty() {
+        return widget::text("You own enough copies to field all your decks at once.").into();
+    }
+
+    let rows = widget::scrollable(widget::column(cards.into_iter().map(|o| {
+        let decks = o
+            .decks
+            .iter()
+            .map(|(name, q)| format!("{} {}", q, name))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        column!(
+            widget::text(format!(
+                "{} - {} short ({} wanted, {} owned)",
+                o.card_name,
+                o.shortage(),
+                o.wanted,
+                o.owned
+            )),
+            widget::text(format!("Used in: {}", decks)),
+        )
+        .into()
+    })))
+    .width(Length::Fill);
 
-    row!(txt_name, btn_view, btn_delete).into()
+    rows.into()
 }
 
-fn view_add_deck(app: &App) -> AppElement {
-    let deck_input = widget::text_editor(&app.deck_input_content)
-        .on_action(AppMessage::EditDeckInput)
-        .height(400);
+fn view_card_detail<'a>(app: &'a App, deck_id: Uuid, card_name: &str) -> AppElement<'a> {
+    let deck = app.decks.get(&deck_id);
+    let card_in_deck = match deck.and_then(|d| d.cards.iter().find(|c| c.card.name == card_name)) {
+        Some(c) => c,
+        None => return widget::text("oopsies").into(),
+    };
+    let card = &card_in_deck.card;
 
-    let btn_analyze = widget::button("Analyze").on_press(AppMessage::AnalyzeDeck);
+    let img = card_image(app, &card.name, 300);
 
-    let output = widget::scrollable(widget::text(&app.deck_output));
+    let btn_back = widget::button(widget::text(format!(
+        "Back to {}",
+        deck.map(|d| d.name.as_str()).unwrap_or_default()
+    )))
+    .on_press(AppMessage::ViewDeck(deck_id));
 
-    let mut column = column!(deck_input, btn_analyze, output);
+    let txt_name = widget::text(&card.name).size(24);
+    let txt_type = widget::text(card.type_line.clone().unwrap_or_default());
+    let txt_mana_cost = widget::text(card.mana_cost.clone().unwrap_or_default());
+    let txt_set = widget::text(format!(
+        "Set: {}",
+        card.set.clone().unwrap_or_default().to_uppercase()
+    ));
+    let txt_price = widget::text(format!(
+        "Price: {}",
+        stats::parse_price(card)
+            .map(|p| format!("${:.2}", p))
+            .unwrap_or("unknown".to_owned())
+    ));
+    let txt_totals = widget::text(format!(
+        "{}/{} in this deck",
+        card_in_deck.current_quantity, card_in_deck.quantity
+    ));
 
-    if app.deck_in_progress.is_some() {
-        let field_deck_name =
-            widget::text_input("Deck name", &app.deck_name).on_input(AppMessage::UpdateDeckName);
-        let btn_create_deck = widget::button("Create deck").on_press(AppMessage::CreateDeck);
+    let info = column!(
+        txt_name,
+        txt_mana_cost,
+        txt_type,
+        txt_set,
+        txt_price,
+        txt_totals,
+        view_card_tags(app, deck_id, card_in_deck)
+    )
+    .spacing(5);
 
-        column = column.push(field_deck_name);
-        column = column.push(btn_create_deck);
-    }
+    let rulings: AppElement = match app.rulings.get(&card.name) {
+        Some(Ok(rulings)) if rulings.is_empty() => widget::text("No rulings").into(),
+        Some(Ok(rulings)) => widget::column(rulings.iter().map(|r| widget::text(r).into()))
+            .spacing(5)
+            .into(),
+        Some(Err(e)) => widget::text(format!("Could not load rulings: {}", e)).into(),
+        None => widget::text("Loading rulings...").into(),
+    };
 
-    column.into()
+    column!(
+        btn_back,
+        row!(img, info).spacing(10),
+        widget::text("Rulings").size(20),
+        widget::scrollable(rulings)
+    )
+    .spacing(5)
+    .into()
 }
 
-fn view_deck_builder(app: &App) -> AppElement {
-    let search_box =
-        widget::text_input("search card...", &app.search_text).on_input(AppMessage::Search);
+fn view_card_tags<'a>(app: &'a App, deck_id: Uuid, card_in_deck: &'a CardInDeck) -> AppElement<'a> {
+    let name = &card_in_deck.card.name;
 
-    let card_results = widget::scrollable(widget::column(
-        app.search_result.iter().map(|c| view_card_result(app, c)),
-    ))
-    .width(Length::Fill);
+    let current_tags = widget::row(card_in_deck.tags.iter().map(|tag| {
+        widget::button(widget::text(format!("{} x", tag)))
+            .style(theme::Button::Secondary)
+            .on_press(AppMessage::RemoveCardTag(
+                deck_id,
+                name.clone(),
+                tag.clone(),
+            ))
+            .into()
+    }))
+    .spacing(5);
+
+    let tag_input = widget::text_input("new tag", &app.tag_input)
+        .on_input(AppMessage::UpdateTagInput)
+        .on_submit(AppMessage::AddCardTag(
+            deck_id,
+            name.clone(),
+            app.tag_input.clone(),
+        ))
+        .width(Length::Fixed(150.));
+
+    let suggestions = widget::row(
+        stats::SUGGESTED_TAGS
+            .iter()
+            .filter(|&&tag| !card_in_deck.tags.iter().any(|t| t == tag))
+            .map(|&tag| {
+                widget::button(widget::text(format!("+ {}", tag)))
+                    .style(theme::Button::Text)
+                    .on_press(AppMessage::AddCardTag(
+                        deck_id,
+                        name.clone(),
+                        tag.to_owned(),
+                    ))
+                    .into()
+            }),
+    );
 
-    column!(search_box, card_results).into()
+    column!(
+        widget::text("Tags"),
+        current_tags,
+        row!(tag_input, suggestions)
+    )
+    .spacing(5)
+    .into()
 }
 
-fn view_card_result<'a>(app: &'a App, card: &'a IndexedCard) -> AppElement<'a> {
-    let img_bytes = app
-        .image_cache
-        .get(&card.name)
-        .unwrap_or(&app.default_image);
-    let img = widget::image::<Handle>(Handle::from_memory(img_bytes.clone()))
-        .content_fit(iced::ContentFit::ScaleDown)
-        .height(100);
+fn view_settings(app: &App) -> AppElement<'_> {
+    let chk_refresh_images = widget::checkbox(
+        "Refresh card images from Scryfall after import",
+        app.settings.refresh_images_on_import,
+    )
+    .on_toggle(|v| AppMessage::UpdateSetting(Setting::RefreshImagesOnImport(v)));
+    let chk_default_quantity = widget::checkbox(
+        "Treat decklist lines without a quantity as 1 copy",
+        app.settings.default_missing_quantity,
+    )
+    .on_toggle(|v| AppMessage::UpdateSetting(Setting::DefaultMissingQuantity(v)));
+    let chk_refresh_prices = widget::checkbox(
+        "Refresh card prices from Scryfall once a day",
+        app.settings.auto_refresh_prices,
+    )
+    .on_toggle(|v| AppMessage::UpdateSetting(Setting::AutoRefreshPrices(v)));
+    let txt_timeout = widget::text(format!(
+        "Scryfall request timeout: {}s",
+        app.settings.request_timeout_secs
+    ));
+    let slider_timeout = widget::slider(5..=120, app.settings.request_timeout_secs, |v| {
+        AppMessage::UpdateSetting(Setting::RequestTimeout(v))
+    })
+    .on_release(AppMessage::SaveSettings)
+    .width(Length::Fixed(300.));
+    let txt_rate = widget::text(format!(
+        "Scryfall requests per second: {}",
+        app.settings.requests_per_second
+    ));
+    let slider_rate = widget::slider(1..=10, app.settings.requests_per_second, |v| {
+        AppMessage::UpdateSetting(Setting::RequestsPerSecond(v))
+    })
+    .on_release(AppMessage::SaveSettings)
+    .width(Length::Fixed(300.));
+    let txt_thumbnail = widget::text(format!(
+        "Card thumbnail height: {}px",
+        app.settings.thumbnail_height
+    ));
+    let slider_thumbnail = widget::slider(40..=300, app.settings.thumbnail_height, |v| {
+        AppMessage::UpdateSetting(Setting::ThumbnailHeight(v))
+    })
+    .on_release(AppMessage::SaveSettings)
+    .width(Length::Fixed(300.));
+    let txt_image_cache = widget::text(format!(
+        "Memory for card images: {} MB",
+        app.settings.image_cache_mb
+    ));
+    let slider_image_cache = widget::slider(16..=1024, app.settings.image_cache_mb, |v| {
+        AppMessage::UpdateSetting(Setting::ImageCacheSize(v))
+    })
+    .on_release(AppMessage::SaveSettings)
+    .width(Length::Fixed(300.));
+    let txt_debounce = widget::text(format!(
+        "Search delay after typing: {}ms",
+        app.settings.search_debounce_ms
+    ));
+    let slider_debounce = widget::slider(0..=1000, app.settings.search_debounce_ms, |v| {
+        AppMessage::UpdateSetting(Setting::SearchDebounce(v))
+    })
+    .step(50u32)
+    .on_release(AppMessage::SaveSettings)
+    .width(Length::Fixed(300.));
+    let txt_results = widget::text(format!(
+        "Card search results per page: {}",
+        app.settings.search_results
+    ));
+    let slider_results = widget::slider(5..=50, app.settings.search_results, |v| {
+        AppMessage::UpdateSetting(Setting::SearchResults(v))
+    })
+    .on_release(AppMessage::SaveSettings)
+    .width(Length::Fixed(300.));
+    let pick_quality = row!(
+        widget::text("Image quality for newly added cards:"),
+        widget::pick_list(ImageQuality::ALL, Some(app.settings.image_quality), |q| {
+            AppMessage::UpdateSetting(Setting::ImageQuality(q))
+        })
+    )
+    .spacing(5)
+    .align_items(iced::Alignment::Center);
+    let pick_paper = row!(
+        widget::text("Paper size for proxy sheets:"),
+        widget::pick_list(PaperSize::ALL, Some(app.settings.paper_size), |p| {
+            AppMessage::UpdateSetting(Setting::PaperSize(p))
+        })
+    )
+    .spacing(5)
+    .align_items(iced::Alignment::Center);
 
-    let deck = app.decks.get(&card.deck_id).unwrap();
-    let card_in_deck = deck.cards.iter().find(|&c| c.card.name == card.name);
-    let card_totals = match card_in_deck {
-        Some(c) => format!("{}/{}", c.current_quantity, c.quantity),
-        None => String::default(),
-    };
-    let card_info = widget::text(format!("{} ({})", card.name, deck.name));
-    let card_totals = widget::text(card_totals);
+    let btn_clear_images =
+        widget::button("Clear image cache").on_press(AppMessage::ClearImageCache);
 
-    let should_allow_add = if card_in_deck.map(|c| c.current_quantity).unwrap_or(0)
-        < card_in_deck.map(|c| c.quantity).unwrap_or(0)
-    {
-        Some(AppMessage::AddCard(card.deck_id, card.name.clone()))
-    } else {
-        None
-    };
-    let should_allow_remove = if card_in_deck.map(|c| c.current_quantity).unwrap_or(0) > 0 {
-        Some(AppMessage::RemoveCard(card.deck_id, card.name.clone()))
-    } else {
-        None
-    };
+    column!(
+        chk_refresh_images,
+        chk_default_quantity,
+        chk_refresh_prices,
+        txt_timeout,
+        slider_timeout,
+        txt_rate,
+        slider_rate,
+        txt_thumbnail,
+        slider_thumbnail,
+        txt_image_cache,
+        slider_image_cache,
+        btn_clear_images,
+        txt_debounce,
+        slider_debounce,
+        txt_results,
+        slider_results,
+        pick_quality,
+        pick_paper,
+        view_name_corrections(app)
+    )
+    .spacing(10)
+    .into()
+}
 
-    let btn_add_card = widget::button("Add card").on_press_maybe(should_allow_add);
-    let btn_remove_card = widget::button("Remove card").on_press_maybe(should_allow_remove);
+fn view_name_corrections(app: &App) -> AppElement<'_> {
+    let input_from = widget::text_input("name in decklist", &app.correction_from)
+        .on_input(AppMessage::UpdateCorrectionFrom)
+        .on_submit(AppMessage::AddNameCorrection);
+    let input_to = widget::text_input("look up as", &app.correction_to)
+        .on_input(AppMessage::UpdateCorrectionTo)
+        .on_submit(AppMessage::AddNameCorrection);
+    let btn_add = widget::button("Add correction").on_press(AppMessage::AddNameCorrection);
 
-    let card_col = column!(card_info, card_totals, row!(btn_add_card, btn_remove_card));
+    let corrections = app.settings.name_corrections.iter().map(|(from, to)| {
+        row!(
+            widget::text(format!("{} -> {}", from, to)).width(Length::Fill),
+            widget::button("Remove").on_press(AppMessage::UpdateSetting(
+                Setting::RemoveNameCorrection(from.clone())
+            ))
+        )
+        .into()
+    });
 
-    row!(img, card_col).into()
+    column!(
+        widget::text("Card name corrections"),
+        row!(input_from, input_to, btn_add).spacing(5),
+        widget::column(corrections)
+    )
+    .spacing(5)
+    .into()
 }
 
-fn view_deck<'a>(app: &'a App, deck_id: Uuid) -> AppElement<'a> {
-    let deck = match app.decks.get(&deck_id) {
-        Some(d) => d,
-        None => return widget::text("oopsies").into(),
-    };
+/// Deck snapshots kept for undo.
+const UNDO_LIMIT: usize = 50;
+/// Quiet time after the last deck change before the decks are saved.
+const SAVE_DEBOUNCE: Duration = Duration::from_secs(1);
 
-    let txt_title = widget::text(&deck.name);
+/// How often the background price refresh checks whether prices are stale.
+const PRICE_REFRESH_CHECK: Duration = Duration::from_secs(60 * 60);
+const PRICE_REFRESH_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+const PRICE_REFRESH_RETRY: Duration = Duration::from_secs(6 * 60 * 60);
 
-    let cards = widget::scrollable(widget::column(
-        deck.cards
-            .iter()
-            .map(|c| view_card_in_deck(app, deck_id, c)),
-    ))
-    .width(Length::Fill);
+/// The card's image, a loading note while it downloads, or the placeholder
+/// with a retry button when it couldn't be downloaded.
+fn card_image<'a>(app: &App, card_name: &str, height: u16) -> AppElement<'a> {
+    if let Some(bytes) = app.image_cache.get(card_name) {
+        return widget::image::<Handle>(Handle::from_memory(bytes.clone()))
+            .content_fit(iced::ContentFit::ScaleDown)
+            .height(height)
+            .into();
+    }
+    if app.loading_images.contains(card_name) {
+        return widget::container(widget::text("Loading..."))
+            .width(Length::Fixed(height as f32 * CARD_ASPECT_RATIO))
+            .height(height)
+            .center_x()
+            .center_y()
+            .style(theme::Container::Box)
+            .into();
+    }
+
+    let placeholder = widget::image::<Handle>(Handle::from_memory(app.default_image.clone()))
+        .content_fit(iced::ContentFit::ScaleDown)
+        .height(height);
+    if app.failed_images.contains(card_name) {
+        return column!(
+            placeholder,
+            widget::button("Retry").on_press(AppMessage::RetryImage(card_name.to_owned()))
+        )
+        .into();
+    }
 
-    column!(txt_title, cards).into()
+    placeholder.into()
 }
 
-fn view_card_in_deck<'a>(
-    app: &'a App,
-    deck_id: Uuid,
-    card_in_deck: &'a CardInDeck,
-) -> AppElement<'a> {
-    let img_bytes = app
-        .image_cache
-        .get(&card_in_deck.card.name)
-        .unwrap_or(&app.default_image);
-    let img = widget::image::<Handle>(Handle::from_memory(img_bytes.clone()))
-        .content_fit(iced::ContentFit::ScaleDown)
-        .height(100);
+const IMAGE_DOWNLOAD_ATTEMPTS: u32 = 3;
+const IMAGE_RETRY_BACKOFF: Duration = Duration::from_millis(300);
 
-    let card_info = widget::text(card_in_deck.card.name.clone());
-    let card_totals = widget::text(format!(
-        "{}/{}",
-        card_in_deck.current_quantity, card_in_deck.quantity
-    ));
+/// Width over height of a card.
+const CARD_ASPECT_RATIO: f32 = 63. / 88.;
+const ZOOMED_CARD_HEIGHT: u16 = 500;
 
-    let should_allow_add = if card_in_deck.current_quantity < card_in_deck.quantity {
-        Some(AppMessage::AddCard(
-            deck_id.clone(),
-            card_in_deck.card.name.clone(),
-        ))
-    } else {
-        None
-    };
-    let should_allow_remove = if card_in_deck.current_quantity > 0 {
-        Some(AppMessage::RemoveCard(
-            deck_id.clone(),
-            card_in_deck.card.name.clone(),
-        ))
-    } else {
-        None
-    };
+const MAX_CARD_NAME_LEN: usize = 32;
 
-    let btn_add_card = widget::button("Add card").on_press_maybe(should_allow_add);
-    let btn_remove_card = widget::button("Remove card").on_press_maybe(should_allow_remove);
+/// Card name cut down to fit a row, with the full name in a tooltip when it
+/// had to be shortened.
+fn card_name_label<'a>(name: &str) -> AppElement<'a> {
+    let graphemes = name.graphemes(true).collect::<Vec<_>>();
+    if graphemes.len() <= MAX_CARD_NAME_LEN {
+        return widget::text(name).into();
+    }
 
-    let card_col = column!(card_info, card_totals, row!(btn_add_card, btn_remove_card));
-    row!(img, card_col).into()
+    let short = format!(
+        "{}…",
+        graphemes[..MAX_CARD_NAME_LEN - 1].concat().trim_end()
+    );
+    widget::tooltip(
+        widget::text(short),
+        widget::container(widget::text(name))
+            .padding(5)
+            .style(theme::Container::Box),
+        widget::tooltip::Position::Bottom,
+    )
+    .into()
+}
+
+/// Reads an exported deck or collection, leaving the current decks untouched
+/// when the file can't be used.
+fn read_deck_file(path: &Path) -> Result<DeckFile, String> {
+    let json = fs::read_to_string(path).map_err(|e| format!("Could not read file: {}", e))?;
+    serde_json::from_str(&json).map_err(|_| {
+        "Could not import: the file is not a deck or collection exported by this app".to_owned()
+    })
+}
+
+/// Carries collected copies, conditions, tags and notes over to a re-analyzed list
+/// for cards that are still in it.
+fn keep_progress(old: &[CardInDeck], mut new: Vec<CardInDeck>) -> Vec<CardInDeck> {
+    for card in new.iter_mut() {
+        if let Some(previous) = old.iter().find(|c| c.card.name == card.card.name) {
+            card.conditions = previous.conditions.clone();
+            card.set_collected(previous.current_quantity);
+            card.tags = previous.tags.clone();
+            card.note = previous.note.clone();
+        }
+    }
+    new
 }
 
-fn build_card_index(deck_id: Uuid, cards: &Vec<CardInDeck>) -> Vec<IndexedCard> {
+fn build_card_index(deck_id: Uuid, cards: &[CardInDeck]) -> Vec<IndexedCard> {
     cards
         .iter()
         .map(|c| IndexedCard {
             name: c.card.name.clone(),
             img: c.card.img.clone(),
-            deck_id: deck_id,
+            img_fallbacks: c.card.img_fallbacks.clone(),
+            deck_id,
         })
         .collect()
 }
 
-fn fuzzy_top_n(query: &str, cards: &Vec<IndexedCard>, top: usize) -> Vec<IndexedCard> {
+/// Compares two versions of a decklist by card name.
+fn diff_cards(old: &[CardInDeck], new: &[CardInDeck]) -> Vec<CardDiff> {
+    let old_quantities = old
+        .iter()
+        .map(|c| (c.card.name.as_str(), c.quantity))
+        .collect::<HashMap<_, _>>();
+    let new_quantities = new
+        .iter()
+        .map(|c| (c.card.name.as_str(), c.quantity))
+        .collect::<HashMap<_, _>>();
+
+    let mut diff = new
+        .iter()
+        .filter_map(|c| match old_quantities.get(c.card.name.as_str()) {
+            None => Some(CardDiff::Added(c.card.name.clone(), c.quantity)),
+            Some(&q) if q != c.quantity => {
+                Some(CardDiff::Changed(c.card.name.clone(), q, c.quantity))
+            }
+            Some(_) => None,
+        })
+        .collect::<Vec<_>>();
+
+    diff.extend(
+        old.iter()
+            .filter(|c| !new_quantities.contains_key(c.card.name.as_str()))
+            .map(|c| CardDiff::Removed(c.card.name.clone(), c.quantity)),
+    );
+
+    diff
+}
+
+fn sort_collection(cards: &mut [CollectionCard], column: CardTableColumn, ascending: bool) {
+    cards.sort_by(|a, b| {
+        let ordering = match column {
+            CardTableColumn::Name => std::cmp::Ordering::Equal,
+            CardTableColumn::Needed => a.needed.cmp(&b.needed),
+            CardTableColumn::Owned => a.owned.cmp(&b.owned),
+            CardTableColumn::Decks => a.deck_ids.len().cmp(&b.deck_ids.len()),
+            CardTableColumn::Price => a.price.unwrap_or(0.).total_cmp(&b.price.unwrap_or(0.)),
+            CardTableColumn::Set => a.set.cmp(&b.set),
+        }
+        .then_with(|| a.name.cmp(&b.name));
+
+        if ascending {
+            ordering
+        } else {
+            ordering.reverse()
+        }
+    });
+}
+
+fn refresh_images(cards: &[IndexedCard], settings: &Settings) -> Command<AppMessage> {
+    let mut names = cards.iter().map(|c| c.name.clone()).collect::<Vec<_>>();
+    names.sort();
+    names.dedup();
+    Command::perform(
+        mtg::refresh_image_urls(names, settings.request_timeout(), settings.image_quality),
+        AppMessage::RefreshImagesFinish,
+    )
+}
+
+/// The `top` best matches for `query`, and how many cards matched at all.
+/// Fuzzy matches come first, followed by cards whose name contains every word
+/// of the query, which catches multi-word names the fuzzy matcher ranks
+/// poorly, like "teferi hero" for "Teferi, Hero of Dominaria".
+fn fuzzy_top_n(query: &str, cards: &[IndexedCard], top: usize) -> (Vec<IndexedCard>, usize) {
     let matcher = SkimMatcherV2::default();
     let mut sorted = cards
         .iter()
-        .map(|c| (c, matcher.fuzzy_match(&c.name, query).unwrap_or(0)))
+        .filter_map(|c| {
+            matcher
+                .fuzzy_match(&c.name, query)
+                .filter(|&score| score > 0)
+                .map(|score| (c, score))
+        })
         .collect::<Vec<_>>();
     sorted.sort_by(|(_, a), (_, b)| b.cmp(a));
 
-    let max_results = if top < sorted.len() {
-        top
-    } else {
-        sorted.len()
-    };
-
-    sorted[0..max_results]
-        .to_vec()
+    let words = query
+        .split_whitespace()
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>();
+    let fuzzy = sorted.into_iter().map(|(c, _)| c).collect::<Vec<_>>();
+    let seen = fuzzy
         .iter()
-        .map(|&(a, _)| a)
-        .cloned()
-        .collect()
+        .map(|c| (c.name.as_str(), c.deck_id))
+        .collect::<HashSet<_>>();
+    let substring = cards.iter().filter(|c| {
+        let name = c.name.to_lowercase();
+        !words.is_empty()
+            && words.iter().all(|w| name.contains(w.as_str()))
+            && !seen.contains(&(c.name.as_str(), c.deck_id))
+    });
+    let combined = fuzzy.iter().copied().chain(substring).collect::<Vec<_>>();
+
+    let matches = combined.len();
+    let top = combined.into_iter().take(top).cloned().collect();
+    (top, matches)
 }
 
-async fn download_image(card_name: &str, card_img: &str) -> (String, Option<Bytes>) {
-    let request = reqwest::get(card_img).await.ok();
-    let img = match request {
-        Some(res) => res.bytes().await.ok(),
-        None => None,
-    };
-    (card_name.to_owned(), img)
+/// Runs a decklist analysis, reporting progress as each card resolves. A new
+/// generation restarts it; `input` and `options` of later calls are ignored.
+fn analyze_decklist(
+    generation: u64,
+    input: String,
+    options: mtg::DecklistOptions,
+) -> Subscription<AppMessage> {
+    iced::subscription::channel(
+        ("analyze_decklist", generation),
+        100,
+        move |mut output| async move {
+            let mut progress = output.clone();
+            let result = mtg::process_decklist(input, options, |resolved, total| {
+                let _ = progress.try_send(AppMessage::AnalyzeDeckProgress(resolved, total));
+            })
+            .await
+            .map_err(|e| e.to_string());
+            let _ = output.send(AppMessage::AnalyzeDeckFinish(result)).await;
+
+            // Dropped as soon as the finish message clears the analysis.
+            std::future::pending().await
+        },
+    )
+}
+
+async fn download_image(
+    card_name: &str,
+    card_img: &str,
+    fallbacks: &[String],
+    timeout: Duration,
+) -> (String, Option<(String, Bytes)>) {
+    let urls = std::iter::once(card_img).chain(fallbacks.iter().map(String::as_str));
+
+    for url in urls.filter(|u| !u.is_empty()) {
+        let cached = storage::load_image(url)
+            .map(Bytes::from)
+            .filter(|bytes| image::load_from_memory(bytes).is_ok());
+        if let Some(bytes) = cached {
+            return (card_name.to_owned(), Some((url.to_owned(), bytes)));
+        }
+
+        let mut backoff = IMAGE_RETRY_BACKOFF;
+        for attempt in 1..=IMAGE_DOWNLOAD_ATTEMPTS {
+            let img = mtg::download(url, timeout).await.ok();
+            // Error pages and truncated downloads count as a failed download
+            // so they're retried too.
+            if let Some(bytes) = img.filter(|bytes| image::load_from_memory(bytes).is_ok()) {
+                if let Err(e) = storage::save_image(url, &bytes) {
+                    eprintln!("could not cache image for {}: {}", card_name, e);
+                }
+                return (card_name.to_owned(), Some((url.to_owned(), bytes)));
+            }
+            if attempt < IMAGE_DOWNLOAD_ATTEMPTS {
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+        }
+    }
+
+    (card_name.to_owned(), None)
+}
+
+/// Downloads print resolution images for `quantity` copies of each card and
+/// writes them out as proxy sheet pages. Returns the number of pages.
+async fn export_proxy_sheet(
+    cards: Vec<(Card, i32)>,
+    timeout: Duration,
+    paper: PaperSize,
+    file: std::path::PathBuf,
+) -> Result<usize, String> {
+    let downloads = cards.iter().map(|(card, _)| async move {
+        let url = card.large_img.as_deref().unwrap_or(&card.img);
+        let mut fallbacks = vec![card.img.clone()];
+        fallbacks.extend(card.img_fallbacks.iter().cloned());
+        download_image(&card.name, url, &fallbacks, timeout)
+            .await
+            .1
+            .map(|(_, bytes)| bytes)
+    });
+    let images = futures::future::join_all(downloads).await;
+
+    let copies = cards
+        .iter()
+        .zip(images)
+        .flat_map(|((_, quantity), image)| std::iter::repeat_n(image, (*quantity).max(0) as usize))
+        .collect::<Vec<_>>();
+
+    tokio::task::spawn_blocking(move || {
+        let pages = proxy::render_pages(&copies, paper);
+        proxy::save_pages(&pages, &file).map(|written| written.len())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())
 }
 
 impl Default for App {
@@ -487,12 +3652,360 @@ impl Default for App {
             deck_input_content: text_editor::Content::new(),
             deck_output: Default::default(),
             deck_in_progress: Default::default(),
+            sideboard_in_progress: Default::default(),
+            deck_diff: Default::default(),
             deck_name: Default::default(),
             search_text: Default::default(),
+            search_generation: 0,
+            search_scope: SearchScope::default(),
+            modifiers: Modifiers::default(),
+            window_size: None,
+            analysis_format: None,
+            analysis: None,
+            analysis_generation: 0,
             card_index: Default::default(),
             search_result: Default::default(),
-            image_cache: Default::default(),
+            search_limit: Settings::default().search_results(),
+            search_match_count: 0,
+            image_cache: ImageCache::new(Settings::default().image_cache_budget()),
+            loading_images: Default::default(),
+            failed_images: Default::default(),
+            zoomed_card: None,
+            large_images: Default::default(),
             default_image: Bytes::from_static(DEFAULT_IMAGE),
+            simulation_target: Default::default(),
+            simulation_turn: 3,
+            simulation: Default::default(),
+            settings: Default::default(),
+            card_table_filter: Default::default(),
+            card_table_sort: CardTableColumn::Name,
+            card_table_ascending: true,
+            shopping_type_filter: Default::default(),
+            focus_mode: false,
+            tag_input: Default::default(),
+            deck_tag_input: Default::default(),
+            deck_tag_filter: None,
+            token_input: Default::default(),
+            new_card_input: Default::default(),
+            new_card_quantity: 1,
+            new_card_output: Default::default(),
+            deck_filter: Default::default(),
+            deck_missing_only: false,
+            deck_sort: SortMode::default(),
+            confirm_delete: None,
+            undo_stack: VecDeque::new(),
+            last_edited_field: None,
+            unsaved_decks: false,
+            save_generation: 0,
+            redo_stack: Vec::new(),
+            rename_input: Default::default(),
+            editing_deck: Default::default(),
+            error_banner: Default::default(),
+            token_output: Default::default(),
+            correction_from: Default::default(),
+            correction_to: Default::default(),
+            price_history: Default::default(),
+            prices_updated_at: Default::default(),
+            price_refresh_failed_at: None,
+            refreshing_prices: false,
+            owned: Default::default(),
+            allocation: Default::default(),
+            rulings: Default::default(),
+        }
+    }
+}
+
+/// Copies collected copies and their conditions from matching imported cards.
+fn import_progress(cards: &mut [CardInDeck], imported: &[CardInDeck]) {
+    for card in cards.iter_mut() {
+        if let Some(imported) = imported.iter().find(|c| c.card.name == card.card.name) {
+            card.conditions = imported.conditions.clone();
+            card.set_collected(imported.current_quantity);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_file(contents: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("mtg-card-organizer-{}.json", Uuid::new_v4()));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn deck(name: &str) -> Deck {
+        serde_json::from_str(&format!(r#"{{"name": "{}", "cards": []}}"#, name)).unwrap()
+    }
+
+    fn indexed(name: &str, deck_id: Uuid) -> IndexedCard {
+        IndexedCard {
+            name: name.to_owned(),
+            img: String::new(),
+            img_fallbacks: Vec::new(),
+            deck_id,
+        }
+    }
+
+    #[test]
+    fn search_without_matches_is_empty() {
+        let deck_id = Uuid::new_v4();
+        let cards = vec![indexed("Forest", deck_id), indexed("Island", deck_id)];
+        let (results, matches) = fuzzy_top_n("qqzzx", &cards, 10);
+        assert!(results.is_empty());
+        assert_eq!(matches, 0);
+    }
+
+    #[test]
+    fn search_falls_back_to_words() {
+        let (control, midrange) = (Uuid::new_v4(), Uuid::new_v4());
+        let cards = vec![
+            indexed("Teferi, Hero of Dominaria", control),
+            indexed("Teferi, Hero of Dominaria", midrange),
+            indexed("Teferi's Protection", control),
+            indexed("Heroic Intervention", midrange),
+            indexed("Forest", midrange),
+        ];
+        let matcher = SkimMatcherV2::default();
+
+        for query in ["teferi hero", "hero teferi"] {
+            let (results, matches) = fuzzy_top_n(query, &cards, 10);
+            assert_eq!(results.len(), matches);
+
+            let teferi = results
+                .iter()
+                .filter(|c| c.name == "Teferi, Hero of Dominaria")
+                .count();
+            assert_eq!(teferi, 2, "{}", query);
+
+            // Fuzzy hits come first, word matches after them.
+            let fuzzy = results
+                .iter()
+                .take_while(|c| matcher.fuzzy_match(&c.name, query).is_some_and(|s| s > 0))
+                .count();
+            assert!(results[fuzzy..]
+                .iter()
+                .all(|c| matcher.fuzzy_match(&c.name, query).is_none_or(|s| s <= 0)));
+
+            let unique = results
+                .iter()
+                .map(|c| (c.name.as_str(), c.deck_id))
+                .collect::<HashSet<_>>();
+            assert_eq!(unique.len(), results.len(), "{}", query);
+        }
+
+        // Words out of order only match through the fallback.
+        assert!(matcher
+            .fuzzy_match("Teferi, Hero of Dominaria", "hero teferi")
+            .is_none());
+    }
+
+    fn app_with_decks(names: &[&str]) -> App {
+        let mut app = App::default();
+        for name in names {
+            app.decks.insert(Uuid::new_v4(), deck(name));
+        }
+        app
+    }
+
+    #[test]
+    fn free_deck_name_is_kept() {
+        let app = app_with_decks(&["Mono Red"]);
+        assert_eq!(app.unique_deck_name("Mono Blue", None), "Mono Blue");
+    }
+
+    #[test]
+    fn taken_deck_names_get_a_number() {
+        let app = app_with_decks(&["Mono Red"]);
+        assert_eq!(app.unique_deck_name("Mono Red", None), "Mono Red (2)");
+
+        let app = app_with_decks(&["Mono Red", "Mono Red (2)", "Mono Red (3)"]);
+        assert_eq!(app.unique_deck_name("Mono Red", None), "Mono Red (4)");
+    }
+
+    #[test]
+    fn renamed_deck_keeps_its_name() {
+        let app = app_with_decks(&["Mono Red"]);
+        let id = *app.decks.keys().next().unwrap();
+        assert_eq!(app.unique_deck_name("Mono Red", Some(id)), "Mono Red");
+    }
+
+    #[test]
+    fn merged_decks_get_unique_names() {
+        let mut app = app_with_decks(&["Mono Red"]);
+        app.merge_decks(vec![deck("Mono Red")]);
+        app.merge_decks(vec![deck("Mono Red"), deck("Mono Red")]);
+
+        let mut names = app
+            .decks
+            .values()
+            .map(|d| d.name.as_str())
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(
+            names,
+            ["Mono Red", "Mono Red (2)", "Mono Red (3)", "Mono Red (4)"]
+        );
+    }
+
+    /// An app with one deck wanting four Forests, none collected.
+    fn app_with_forests() -> (App, Uuid) {
+        let mut app = App::default();
+        let deck_id = Uuid::new_v4();
+        let deck = serde_json::from_str(
+            r#"{"name": "Mono Green", "cards": [{"quantity": 4, "current_quantity": 0,
+                "card": {"name": "Forest", "img": ""}}]}"#,
+        )
+        .unwrap();
+        app.decks.insert(deck_id, deck);
+        (app, deck_id)
+    }
+
+    #[test]
+    fn allocating_owned_cards_is_saved_and_undoable() {
+        let (mut app, deck_id) = app_with_forests();
+        app.owned.insert("Forest".to_owned(), 3);
+
+        let _ = app.update(AppMessage::AllocateOwned);
+        assert_eq!(app.decks[&deck_id].cards[0].current_quantity, 3);
+        assert!(app.unsaved_decks);
+        assert_eq!(app.undo_stack.len(), 1);
+
+        let _ = app.update(AppMessage::Undo);
+        assert_eq!(app.decks[&deck_id].cards[0].current_quantity, 0);
+    }
+
+    #[test]
+    fn typing_into_a_field_undoes_at_once() {
+        let (mut app, deck_id) = app_with_forests();
+        let note = |app: &App| app.decks[&deck_id].cards[0].note.clone();
+
+        for typed in ["r", "ra", "ram"] {
+            let _ = app.update(AppMessage::SetCardNote(
+                deck_id,
+                "Forest".to_owned(),
+                typed.to_owned(),
+            ));
+        }
+        let _ = app.update(AppMessage::AddCard(deck_id, "Forest".to_owned()));
+        let _ = app.update(AppMessage::SetCardNote(
+            deck_id,
+            "Forest".to_owned(),
+            "ramp".to_owned(),
+        ));
+        assert_eq!(app.undo_stack.len(), 3);
+
+        let _ = app.update(AppMessage::Undo);
+        assert_eq!(note(&app).as_deref(), Some("ram"));
+        let _ = app.update(AppMessage::Undo);
+        assert_eq!(app.decks[&deck_id].cards[0].current_quantity, 0);
+        let _ = app.update(AppMessage::Undo);
+        assert_eq!(note(&app), None);
+    }
+
+    #[test]
+    fn imported_progress_keeps_conditions_within_collected_copies() {
+        let (mut app, deck_id) = app_with_forests();
+        let mut imported = app.decks[&deck_id].cards.clone();
+        imported[0].quantity = 8;
+        imported[0].current_quantity = 6;
+        imported[0].conditions = vec![Some(CardCondition::NearMint); 6];
+
+        import_progress(&mut app.decks.get_mut(&deck_id).unwrap().cards, &imported);
+        let card = &app.decks[&deck_id].cards[0];
+        assert_eq!(card.current_quantity, 4);
+        assert_eq!(card.conditions, vec![Some(CardCondition::NearMint); 4]);
+    }
+
+    #[test]
+    fn leaving_the_editor_keeps_editing_the_deck() {
+        let (mut app, deck_id) = app_with_forests();
+        let _ = app.update(AppMessage::EditDeck(deck_id));
+        let _ = app.update(AppMessage::ChangeSection(Section::Decks));
+        let _ = app.update(AppMessage::ChangeSection(Section::AddDeck));
+        let _ = app.update(AppMessage::CreateDeck);
+
+        assert_eq!(app.decks.len(), 1);
+        assert_eq!(app.decks[&deck_id].name, "Mono Green");
+        assert_eq!(app.editing_deck, None);
+    }
+
+    #[test]
+    fn cancelled_edit_is_forgotten() {
+        let (mut app, deck_id) = app_with_forests();
+        let _ = app.update(AppMessage::EditDeck(deck_id));
+        let _ = app.update(AppMessage::CancelEdit);
+
+        assert_eq!(app.editing_deck, None);
+        assert!(app.deck_in_progress.is_none());
+        assert!(app.deck_name.is_empty());
+        assert!(app.deck_input_content.text().trim().is_empty());
+    }
+
+    #[test]
+    fn failed_price_refresh_is_quiet_and_backs_off() {
+        let (mut app, _) = app_with_forests();
+        let _ = app.update(AppMessage::RefreshPricesFinish(Err("offline".to_owned())));
+        assert!(app.error_banner.is_none());
+        assert!(app.price_refresh_failed_at.is_some());
+
+        let _ = app.update(AppMessage::PriceRefreshTick);
+        assert!(!app.refreshing_prices);
+    }
+
+    #[test]
+    fn spamming_add_and_remove_stays_in_bounds() {
+        let (mut app, deck_id) = app_with_forests();
+        let forest = |app: &App| app.decks[&deck_id].cards[0].clone();
+
+        for copy in 0..10 {
+            let _ = app.update(AppMessage::AddCard(deck_id, "Forest".to_owned()));
+            let _ = app.update(AppMessage::SetCardCondition(
+                deck_id,
+                "Forest".to_owned(),
+                copy,
+                CardCondition::LightlyPlayed,
+            ));
+            let card = forest(&app);
+            assert!((0..=4).contains(&card.current_quantity));
+            assert!(card.conditions.len() <= card.current_quantity as usize);
         }
+        assert_eq!(forest(&app).current_quantity, 4);
+        assert_eq!(forest(&app).conditions.len(), 4);
+
+        for _ in 0..10 {
+            let _ = app.update(AppMessage::RemoveCard(deck_id, "Forest".to_owned()));
+            let card = forest(&app);
+            assert!((0..=4).contains(&card.current_quantity));
+            assert!(card.conditions.len() <= card.current_quantity as usize);
+        }
+        assert_eq!(forest(&app).current_quantity, 0);
+        assert!(forest(&app).conditions.is_empty());
+    }
+
+    #[test]
+    fn garbage_deck_file_is_rejected() {
+        for contents in ["not json", "{\"name\": ", "[1, 2, 3]"] {
+            let path = temp_file(contents);
+            let result = read_deck_file(&path);
+            fs::remove_file(&path).unwrap();
+            assert!(result.is_err(), "{} was accepted", contents);
+        }
+    }
+
+    #[test]
+    fn deck_and_collection_files_parse() {
+        let single = serde_json::to_string(&deck("Mono Red")).unwrap();
+        let path = temp_file(&single);
+        let result = read_deck_file(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Ok(DeckFile::Single(d)) if d.name == "Mono Red"));
+
+        let collection = HashMap::from([(Uuid::new_v4(), deck("Mono Red"))]);
+        let path = temp_file(&serde_json::to_string(&collection).unwrap());
+        let result = read_deck_file(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Ok(DeckFile::Collection(decks)) if decks.len() == 1));
     }
 }
@@ -0,0 +1,92 @@
+use std::collections::{HashMap, VecDeque};
+
+use bytes::Bytes;
+
+/// Card images kept in memory, keyed by card name. Once the images take more
+/// than the budget, the least recently used ones are dropped; they're
+/// downloaded again (or read from disk) the next time they're needed.
+pub struct ImageCache {
+    images: HashMap<String, Bytes>,
+    /// Card names from least to most recently used.
+    order: VecDeque<String>,
+    bytes: usize,
+    budget: usize,
+}
+
+impl ImageCache {
+    pub fn new(budget: usize) -> Self {
+        Self {
+            images: HashMap::new(),
+            order: VecDeque::new(),
+            bytes: 0,
+            budget,
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Bytes> {
+        self.images.get(name)
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.images.contains_key(name)
+    }
+
+    /// Marks an image as just used so it's evicted last.
+    pub fn touch(&mut self, name: &str) {
+        if let Some(position) = self.order.iter().position(|n| n == name) {
+            if let Some(n) = self.order.remove(position) {
+                self.order.push_back(n);
+            }
+        }
+    }
+
+    pub fn insert(&mut self, name: String, image: Bytes) {
+        self.remove(&name);
+        self.bytes += image.len();
+        self.order.push_back(name.clone());
+        self.images.insert(name, image);
+        self.evict();
+    }
+
+    pub fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) {
+        let removed = self
+            .order
+            .iter()
+            .filter(|n| !keep(n))
+            .cloned()
+            .collect::<Vec<_>>();
+        for name in removed {
+            self.remove(&name);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.images.clear();
+        self.order.clear();
+        self.bytes = 0;
+    }
+
+    pub fn set_budget(&mut self, budget: usize) {
+        self.budget = budget;
+        self.evict();
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        if let Some(image) = self.images.remove(name) {
+            self.bytes -= image.len();
+            self.order.retain(|n| n != name);
+        }
+    }
+
+    /// Drops the least recently used images until the cache fits its budget,
+    /// always keeping the newest one.
+    fn evict(&mut self) {
+        while self.bytes > self.budget && self.order.len() > 1 {
+            if let Some(name) = self.order.pop_front() {
+                if let Some(image) = self.images.remove(&name) {
+                    self.bytes -= image.len();
+                }
+            }
+        }
+    }
+}
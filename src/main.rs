@@ -1,14 +1,27 @@
 #![windows_subsystem = "windows"]
 
-use app::App;
-use iced::{Application, Settings};
+use app::{App, UiState};
+use iced::{Application, Settings, Size};
 
 mod app;
+mod image_cache;
 mod models;
 mod mtg;
+mod proxy;
+mod settings;
+mod stats;
+mod storage;
 
 fn main() {
-    let settings = Settings::with_flags(());
+    let ui_state: UiState = storage::load_json(storage::UI_STATE_FILE).unwrap_or_default();
+    let window_size = ui_state.window_size;
+
+    let mut settings = Settings::with_flags(ui_state);
+    // The app saves where the user left off before closing the window itself.
+    settings.window.exit_on_close_request = false;
+    if let Some((width, height)) = window_size {
+        settings.window.size = Size::new(width as f32, height as f32);
+    }
 
     App::run(settings).unwrap();
 }
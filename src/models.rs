@@ -1,28 +1,349 @@
-use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use serde::{Deserialize, Deserializer, Serialize};
 use uuid::Uuid;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct Card {
     pub name: String,
     pub img: String,
+    #[serde(default)]
+    pub id: Option<Uuid>,
+    #[serde(default)]
+    pub type_line: Option<String>,
+    #[serde(default)]
+    pub set: Option<String>,
+    #[serde(default)]
+    pub usd_price: Option<String>,
+    #[serde(default)]
+    pub cmc: Option<f32>,
+    #[serde(default)]
+    pub mana_cost: Option<String>,
+    /// Color letters as Scryfall writes them, e.g. `["U", "R"]`.
+    #[serde(default)]
+    pub colors: Option<Vec<String>>,
+    #[serde(default)]
+    pub legalities: HashMap<GameFormat, Legality>,
+    /// Image URLs tried in order when `img` fails to download.
+    #[serde(default)]
+    pub img_fallbacks: Vec<String>,
+    /// Back face image of double-faced cards.
+    #[serde(default)]
+    pub back_img: Option<String>,
+    /// High resolution image for zooming in on the card.
+    #[serde(default)]
+    pub large_img: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum GameFormat {
+    Standard,
+    Pioneer,
+    Modern,
+    Legacy,
+    Vintage,
+    Pauper,
+    Commander,
+}
+
+impl GameFormat {
+    pub const ALL: [GameFormat; 7] = [
+        GameFormat::Standard,
+        GameFormat::Pioneer,
+        GameFormat::Modern,
+        GameFormat::Legacy,
+        GameFormat::Vintage,
+        GameFormat::Pauper,
+        GameFormat::Commander,
+    ];
+}
+
+impl std::fmt::Display for GameFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            GameFormat::Standard => "Standard",
+            GameFormat::Pioneer => "Pioneer",
+            GameFormat::Modern => "Modern",
+            GameFormat::Legacy => "Legacy",
+            GameFormat::Vintage => "Vintage",
+            GameFormat::Pauper => "Pauper",
+            GameFormat::Commander => "Commander",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Preferred size of the card images saved with resolved cards.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageQuality {
+    Small,
+    #[default]
+    Normal,
+    Large,
+}
+
+impl ImageQuality {
+    pub const ALL: [ImageQuality; 3] = [
+        ImageQuality::Small,
+        ImageQuality::Normal,
+        ImageQuality::Large,
+    ];
+
+    /// Scryfall image sizes in the order they're tried.
+    pub fn sizes(&self) -> [&'static str; 5] {
+        match self {
+            ImageQuality::Small => ["small", "normal", "large", "png", "art_crop"],
+            ImageQuality::Normal => ["normal", "large", "png", "small", "art_crop"],
+            ImageQuality::Large => ["large", "png", "normal", "small", "art_crop"],
+        }
+    }
+}
+
+impl std::fmt::Display for ImageQuality {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            ImageQuality::Small => "Small",
+            ImageQuality::Normal => "Normal",
+            ImageQuality::Large => "Large",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Paper that proxy sheets are laid out for.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PaperSize {
+    #[default]
+    Letter,
+    A4,
+}
+
+impl PaperSize {
+    pub const ALL: [PaperSize; 2] = [PaperSize::Letter, PaperSize::A4];
+}
+
+impl std::fmt::Display for PaperSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            PaperSize::Letter => "Letter",
+            PaperSize::A4 => "A4",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Legality {
+    Legal,
+    NotLegal,
+    Restricted,
+    Banned,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct Deck {
     pub name: String,
-    pub cards: Vec<CardInDeck>
+    pub cards: Vec<CardInDeck>,
+    #[serde(default)]
+    pub format: Option<GameFormat>,
+    #[serde(default)]
+    pub tokens: Vec<CardInDeck>,
+    #[serde(default)]
+    pub sideboard: Vec<CardInDeck>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Place in the deck list; ties are ordered by name.
+    #[serde(default)]
+    pub position: u32,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct CardInDeck {
     pub quantity: i32,
     pub current_quantity: i32,
-    pub card: Card
+    pub card: Card,
+    /// Condition of each collected copy, in the order they were collected.
+    #[serde(
+        default,
+        alias = "condition",
+        deserialize_with = "deserialize_conditions"
+    )]
+    pub conditions: Vec<Option<CardCondition>>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+impl CardInDeck {
+    /// Sets how many copies are collected, kept within `0..=quantity`.
+    pub fn set_collected(&mut self, count: i32) {
+        self.current_quantity = count.min(self.quantity).max(0);
+        self.conditions.truncate(self.current_quantity as usize);
+    }
+
+    pub fn copy_condition(&self, copy: usize) -> Option<CardCondition> {
+        self.conditions.get(copy).copied().flatten()
+    }
+
+    /// Sets the condition of one collected copy; copies not collected yet are
+    /// left alone.
+    pub fn set_copy_condition(&mut self, copy: usize, condition: Option<CardCondition>) {
+        if copy >= self.current_quantity.max(0) as usize {
+            return;
+        }
+        if self.conditions.len() <= copy {
+            self.conditions.resize(copy + 1, None);
+        }
+        self.conditions[copy] = condition;
+    }
+}
+
+/// Reads per copy conditions, or the single condition older saves kept for the
+/// whole entry, which is given to the first copy.
+fn deserialize_conditions<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<Option<CardCondition>>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Conditions {
+        PerCopy(Vec<Option<CardCondition>>),
+        Entry(Option<CardCondition>),
+    }
+
+    Ok(match Conditions::deserialize(deserializer)? {
+        Conditions::PerCopy(conditions) => conditions,
+        Conditions::Entry(condition) => condition.into_iter().map(Some).collect(),
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum CardCondition {
+    NearMint,
+    LightlyPlayed,
+    ModeratelyPlayed,
+    HeavilyPlayed,
+    Damaged,
+}
+
+impl CardCondition {
+    pub const ALL: [CardCondition; 5] = [
+        CardCondition::NearMint,
+        CardCondition::LightlyPlayed,
+        CardCondition::ModeratelyPlayed,
+        CardCondition::HeavilyPlayed,
+        CardCondition::Damaged,
+    ];
+}
+
+impl std::fmt::Display for CardCondition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            CardCondition::NearMint => "NM",
+            CardCondition::LightlyPlayed => "LP",
+            CardCondition::ModeratelyPlayed => "MP",
+            CardCondition::HeavilyPlayed => "HP",
+            CardCondition::Damaged => "DMG",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Either a full collection export or a single exported deck.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum DeckFile {
+    Collection(HashMap<Uuid, Deck>),
+    Single(Deck),
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct IndexedCard{
+pub struct PriceSnapshot {
+    pub timestamp: i64,
+    pub total_value: f32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IndexedCard {
     pub name: String,
     pub img: String,
-    pub deck_id: Uuid
-}
\ No newline at end of file
+    #[serde(default)]
+    pub img_fallbacks: Vec<String>,
+    pub deck_id: Uuid,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn card_in_deck(quantity: i32) -> CardInDeck {
+        let card = serde_json::from_str(r#"{"name": "Forest", "img": ""}"#).unwrap();
+        CardInDeck {
+            quantity,
+            current_quantity: 0,
+            card,
+            conditions: Vec::new(),
+            tags: Vec::new(),
+            note: None,
+        }
+    }
+
+    #[test]
+    fn conditions_are_kept_per_copy() {
+        let mut card = card_in_deck(4);
+        card.set_copy_condition(0, Some(CardCondition::NearMint));
+        assert_eq!(card.copy_condition(0), None);
+
+        card.set_collected(3);
+        card.set_copy_condition(0, Some(CardCondition::NearMint));
+        card.set_copy_condition(2, Some(CardCondition::Damaged));
+        assert_eq!(card.copy_condition(0), Some(CardCondition::NearMint));
+        assert_eq!(card.copy_condition(1), None);
+        assert_eq!(card.copy_condition(2), Some(CardCondition::Damaged));
+
+        card.set_collected(1);
+        assert_eq!(card.conditions, vec![Some(CardCondition::NearMint)]);
+    }
+
+    #[test]
+    fn single_condition_of_old_saves_loads() {
+        let card: CardInDeck = serde_json::from_str(
+            r#"{"quantity": 2, "current_quantity": 2, "condition": "LightlyPlayed",
+                "card": {"name": "Forest", "img": ""}}"#,
+        )
+        .unwrap();
+        assert_eq!(card.conditions, vec![Some(CardCondition::LightlyPlayed)]);
+
+        let card: CardInDeck = serde_json::from_str(
+            r#"{"quantity": 2, "current_quantity": 2, "condition": null,
+                "card": {"name": "Forest", "img": ""}}"#,
+        )
+        .unwrap();
+        assert!(card.conditions.is_empty());
+    }
+
+    #[test]
+    fn collected_copies_stay_in_bounds() {
+        let mut card = card_in_deck(4);
+        for _ in 0..10 {
+            card.set_collected(card.current_quantity + 1);
+            assert!((0..=4).contains(&card.current_quantity));
+        }
+        assert_eq!(card.current_quantity, 4);
+
+        for _ in 0..10 {
+            card.set_collected(card.current_quantity - 1);
+            assert!((0..=4).contains(&card.current_quantity));
+        }
+        assert_eq!(card.current_quantity, 0);
+
+        card.set_collected(99);
+        assert_eq!(card.current_quantity, 4);
+        card.set_collected(-3);
+        assert_eq!(card.current_quantity, 0);
+    }
+}
@@ -1,8 +1,68 @@
-use crate::models::{Card, CardInDeck};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Mutex, OnceLock,
+    },
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+
+use futures::{stream, StreamExt};
+use reqwest::header::{self, HeaderMap, HeaderValue};
+use scryfall::{error::ScryfallError, list::List, ruling::Ruling};
+use serde::de::DeserializeOwned;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::models::{Card, CardInDeck, GameFormat, ImageQuality, Legality};
+
+const API_URL: &str = "https://api.scryfall.com";
+/// Card lookups in flight at once while analyzing a decklist.
+const CONCURRENT_LOOKUPS: usize = 10;
+/// Attempts made for a request Scryfall answers with 429 Too Many Requests.
+const MAX_ATTEMPTS: u32 = 4;
+const RETRY_BACKOFF: Duration = Duration::from_millis(500);
+
+static REQUESTS_PER_SECOND: AtomicU32 = AtomicU32::new(10);
+const USER_AGENT: &str = concat!("mtg-card-organizer/", env!("CARGO_PKG_VERSION"));
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Timed out waiting for Scryfall, analyze again to retry")]
+    Timeout,
+    #[error("Could not reach Scryfall: {0}")]
+    Network(reqwest::Error),
+    #[error("{}", .0.details)]
+    Scryfall(ScryfallError),
+    #[error("Scryfall answered with HTTP {0}")]
+    Http(reqwest::StatusCode),
+    #[error("Unexpected response from Scryfall: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("No match found for {0}")]
+    NotFound(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// Scryfall has no card by that name.
+    NotFound,
+    /// The name matches more than one card.
+    Ambiguous,
+    /// Scryfall couldn't be reached or answered with something unexpected.
+    Network,
+    /// The decklist itself is wrong, e.g. a bad quantity or a rules violation.
+    Invalid,
+}
+
 #[derive(Clone, Debug)]
 pub struct CardErrorInsight {
     pub card_name: String,
     pub error: String,
+    pub kind: ErrorKind,
 }
 
 impl CardErrorInsight {
@@ -10,47 +70,1140 @@ impl CardErrorInsight {
         Self {
             card_name: card_name.to_owned(),
             error: error.to_owned(),
+            kind: ErrorKind::Invalid,
+        }
+    }
+
+    pub fn not_found(card_name: &str) -> Self {
+        Self {
+            kind: ErrorKind::NotFound,
+            ..Self::new(card_name, &format!("No card matches '{}'", card_name))
         }
     }
+
+    /// Explains why looking `card_name` up failed in terms a player can act on.
+    pub fn from_error(card_name: &str, error: &Error) -> Self {
+        match error {
+            Error::NotFound(_) => Self::not_found(card_name),
+            Error::Scryfall(e) if e.error_type.as_deref() == Some("ambiguous") => Self {
+                kind: ErrorKind::Ambiguous,
+                ..Self::new(
+                    card_name,
+                    &format!("'{}' matches several cards, be more specific", card_name),
+                )
+            },
+            Error::Scryfall(e) if e.code == "not_found" => Self::not_found(card_name),
+            Error::Scryfall(_) => Self::new(card_name, &error.to_string()),
+            Error::Network(_) => Self {
+                kind: ErrorKind::Network,
+                ..Self::new(card_name, "Network error, check your connection")
+            },
+            Error::Timeout | Error::Http(_) | Error::Json(_) => Self {
+                kind: ErrorKind::Network,
+                ..Self::new(card_name, &error.to_string())
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct AnalyzedDecklist {
+    pub cards: Vec<CardInDeck>,
+    pub sideboard: Vec<CardInDeck>,
+    pub errors: Vec<CardErrorInsight>,
+}
+
+#[derive(Clone, Debug)]
+pub struct DecklistOptions {
+    pub default_missing_quantity: bool,
+    pub timeout: Duration,
+    pub name_corrections: BTreeMap<String, String>,
+    /// Format whose deck construction rules are checked, if any.
+    pub format: Option<GameFormat>,
+    pub image_quality: ImageQuality,
+}
+
+/// Looks up every card of a decklist. Cards that can't be found end up as
+/// insights; losing the connection to Scryfall aborts the whole analysis.
+/// `on_progress` is called with `(resolved, total)` as lookups finish.
+pub async fn process_decklist(
+    decklist: String,
+    options: DecklistOptions,
+    mut on_progress: impl FnMut(usize, usize),
+) -> Result<AnalyzedDecklist> {
+    let card_list = parse_decklist(&decklist, options.default_missing_quantity);
+
+    let options = &options;
+    prefetch_by_name(&card_list, options).await?;
+
+    // `buffered` keeps results in decklist order, so errors are listed in the
+    // same order every time.
+
+    let total = card_list.len();
+    let mut resolved = 0;
+    let lookups = stream::iter(card_list)
+        .map(|card| async move {
+            if let Some(error) = line_error(card.0, &card.1) {
+                return (card, Err(error));
+            }
+            let result = resolve_card(&card.1, card.0, options).await;
+            (card, Ok(result))
+        })
+        .buffered(CONCURRENT_LOOKUPS)
+        .inspect(|_| {
+            resolved += 1;
+            on_progress(resolved, total);
+        })
+        .collect::<Vec<_>>()
+        .await;
+
+    let mut analyzed = AnalyzedDecklist::default();
+
+    for (card, result) in lookups {
+        let result = match result {
+            Ok(r) => r,
+            Err(error) => {
+                analyzed.errors.push(error);
+                continue;
+            }
+        };
+
+        let section = if card.2 {
+            &mut analyzed.sideboard
+        } else {
+            &mut analyzed.cards
+        };
+        match result {
+            Ok(c) => merge_card(section, c),
+            Err(e @ Error::Network(_)) => return Err(e),
+            Err(e) => analyzed
+                .errors
+                .push(CardErrorInsight::from_error(&card.1, &e)),
+        };
+    }
+
+    if let Some(format) = options.format {
+        let violations = format_violations(&analyzed.cards, format);
+        analyzed.errors.extend(violations);
+    }
+
+    Ok(analyzed)
+}
+
+/// Adds a resolved card to a deck section. Lines naming the same card, even
+/// spelled differently, add up.
+fn merge_card(section: &mut Vec<CardInDeck>, card: CardInDeck) {
+    match section
+        .iter_mut()
+        .find(|existing| existing.card.name == card.card.name)
+    {
+        Some(existing) => existing.quantity += card.quantity,
+        None => section.push(card),
+    }
+}
+
+/// Why a parsed decklist line can't be looked up, if it can't.
+fn line_error(quantity: i32, entry: &str) -> Option<CardErrorInsight> {
+    if entry.is_empty() {
+        // A line with only a quantity, such as `4`, names no card.
+        Some(CardErrorInsight::new(
+            &quantity.to_string(),
+            "Missing card name",
+        ))
+    } else if quantity <= 0 {
+        Some(CardErrorInsight::new(entry, "Invalid quantity"))
+    } else {
+        None
+    }
 }
 
-pub async fn process_decklist(decklist: String) -> (Vec<CardInDeck>, Vec<CardErrorInsight>) {
-    let card_list = decklist.lines().filter(|&l| !l.trim().is_empty()).map(|l| {
-        let mut split = l.split_whitespace();
-        let quantity: i32 = split.next().unwrap_or("0").parse().unwrap_or(0);
-        let name = split.collect::<Vec<_>>().join(" ");
-        (quantity, name)
-    });
+/// Most cards a sideboard set apart only by a blank line can have.
+const SIDEBOARD_SIZE: i32 = 15;
 
-    let mut cards_in_deck = Vec::<CardInDeck>::new();
-    let mut errors = Vec::<CardErrorInsight>::new();
+/// Splits a decklist into `(quantity, entry, in_sideboard)` lines. A
+/// `Sideboard` header starts the sideboard. Without one, as in MTGO exports,
+/// the last blank line separated block is the sideboard if it has at most
+/// `SIDEBOARD_SIZE` cards, unless it completes a 100 card Commander deck,
+/// since Commander lists set the commander apart the same way. `Deck` headers
+/// and `//` comments are skipped.
+fn parse_decklist(decklist: &str, default_missing_quantity: bool) -> Vec<(i32, String, bool)> {
+    let mut card_list = Vec::new();
+    let mut in_sideboard = false;
+    let mut has_header = false;
+    let mut last_block = 0;
+    let mut after_blank = false;
 
-    for card in card_list {
-        if card.0 == 0 {
-            errors.push(CardErrorInsight::new(&card.1, "Invalid quantity"));
+    for line in decklist.lines().map(str::trim) {
+        let header = line.trim_end_matches(':').to_lowercase();
+        if header == "sideboard" {
+            in_sideboard = true;
+            has_header = true;
             continue;
         }
+        if line.is_empty() {
+            after_blank = true;
+            continue;
+        }
+        if header == "deck" || line.starts_with("//") {
+            continue;
+        }
+
+        if after_blank {
+            last_block = card_list.len();
+            after_blank = false;
+        }
+        let (quantity, name) = parse_line(line, default_missing_quantity);
+        card_list.push((quantity, name, in_sideboard));
+    }
+
+    if !has_header && last_block > 0 {
+        let count = |lines: &[(i32, String, bool)]| lines.iter().map(|(q, _, _)| q).sum::<i32>();
+        let block = count(&card_list[last_block..]);
+        if block <= SIDEBOARD_SIZE && count(&card_list) != COMMANDER_DECK_SIZE {
+            for line in &mut card_list[last_block..] {
+                line.2 = true;
+            }
+        }
+    }
+    card_list
+}
+
+const COMMANDER_DECK_SIZE: i32 = 100;
+
+/// Deck construction rules of `format` that the resolved cards break: card
+/// legality, plus Commander's size and singleton rules.
+pub fn format_violations(cards: &[CardInDeck], format: GameFormat) -> Vec<CardErrorInsight> {
+    let mut violations = Vec::new();
+    for c in cards {
+        let error = match c.card.legalities.get(&format) {
+            Some(Legality::Banned) => format!("Banned in {}", format),
+            Some(Legality::NotLegal) => format!("Not legal in {}", format),
+            Some(Legality::Restricted) if c.quantity > 1 => {
+                format!("Restricted in {}, found {} copies", format, c.quantity)
+            }
+            _ => continue,
+        };
+        violations.push(CardErrorInsight::new(&c.card.name, &error));
+    }
+    if format != GameFormat::Commander {
+        return violations;
+    }
+
+    let total: i32 = cards.iter().map(|c| c.quantity).sum();
+    if total != COMMANDER_DECK_SIZE {
+        violations.push(CardErrorInsight::new(
+            "Deck",
+            &format!(
+                "Commander decks have {} cards, this one has {}",
+                COMMANDER_DECK_SIZE, total
+            ),
+        ));
+    }
+
+    for c in cards {
+        let basic = c
+            .card
+            .type_line
+            .as_ref()
+            .is_some_and(|t| t.contains("Basic"));
+        if c.quantity > 1 && !basic {
+            violations.push(CardErrorInsight::new(
+                &c.card.name,
+                &format!("Commander allows one copy, found {}", c.quantity),
+            ));
+        }
+    }
+    violations
+}
+
+/// Splits a decklist line into quantity and card name. The quantity may be
+/// written as `4` or `4x`. Lines that don't start with a number are taken as a
+/// single copy of the whole line when `default_missing_quantity` is set, and
+/// as an invalid quantity otherwise.
+pub fn parse_line(line: &str, default_missing_quantity: bool) -> (i32, String) {
+    let mut split = line.split_whitespace();
+    let first = split.next().unwrap_or_default();
+    let quantity = first
+        .strip_suffix(['x', 'X'])
+        .filter(|q| !q.is_empty())
+        .unwrap_or(first);
+
+    match quantity.parse() {
+        Ok(quantity) => (quantity, split.collect::<Vec<_>>().join(" ")),
+        Err(_) if default_missing_quantity => {
+            (1, line.split_whitespace().collect::<Vec<_>>().join(" "))
+        }
+        Err(_) => (0, split.collect::<Vec<_>>().join(" ")),
+    }
+}
+
+fn lookup_cache() -> &'static Mutex<HashMap<String, Card>> {
+    static CACHE: OnceLock<Mutex<HashMap<String, Card>>> = OnceLock::new();
+    CACHE.get_or_init(Default::default)
+}
+
+/// Seeds the decklist lookup cache, e.g. with one saved by a previous session.
+/// Entries saved before lookups were keyed by image quality are dropped.
+pub fn load_lookup_cache(cards: HashMap<String, Card>) {
+    lookup_cache()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .extend(cards.into_iter().filter(|(key, _)| {
+            ImageQuality::ALL
+                .iter()
+                .any(|q| key.starts_with(&format!("{}:", q)))
+        }));
+}
+
+/// Every lookup resolved so far, keyed by normalized decklist text.
+pub fn lookup_cache_snapshot() -> HashMap<String, Card> {
+    lookup_cache()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
+
+/// Resolves a single decklist entry, such as `Lightning Bolt` or
+/// `Lightning Bolt (2XM) 129`, into `quantity` copies of the card. Name
+/// corrections from `options` are applied first.
+pub async fn resolve_card(
+    entry: &str,
+    quantity: i32,
+    options: &DecklistOptions,
+) -> Result<CardInDeck> {
+    let (name, printing) = corrected_entry(entry, options);
+    let card = lookup_card(&name, printing.as_ref(), options).await?;
+    Ok(CardInDeck {
+        quantity,
+        current_quantity: 0,
+        card,
+        conditions: Vec::new(),
+        tags: Vec::new(),
+        note: None,
+    })
+}
+
+/// Card name and printing of a decklist entry, after name corrections.
+fn corrected_entry(entry: &str, options: &DecklistOptions) -> (String, Option<(String, String)>) {
+    let (name, printing) = split_printing(entry);
+    match options.name_corrections.get(&name.to_lowercase()) {
+        Some(corrected) => (corrected.clone(), printing),
+        None => (name, printing),
+    }
+}
+
+/// Normalized decklist text, prefixed by the image quality since cached cards
+/// keep the image URLs picked for it.
+fn lookup_key(name: &str, printing: Option<&(String, String)>, quality: ImageQuality) -> String {
+    let mut key = format!(
+        "{}:{}",
+        quality,
+        name.split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase()
+    );
+    if let Some((set, number)) = printing {
+        key.push_str(&format!(" ({}) {}", set, number));
+    }
+    key
+}
+
+/// Resolves the exactly spelled names of a decklist through the collection
+/// endpoint, 75 per request, and stores them in the lookup cache. Whatever it
+/// doesn't find is left for the fuzzy, one card at a time lookup.
+async fn prefetch_by_name(
+    card_list: &[(i32, String, bool)],
+    options: &DecklistOptions,
+) -> Result<()> {
+    let mut identifiers = {
+        let cache = lookup_cache().lock().unwrap_or_else(|e| e.into_inner());
+        card_list
+            .iter()
+            .filter(|(quantity, entry, _)| line_error(*quantity, entry).is_none())
+            .map(|(_, entry, _)| corrected_entry(entry, options))
+            .filter(|(name, printing)| {
+                printing.is_none()
+                    && !cache.contains_key(&lookup_key(name, None, options.image_quality))
+            })
+            .map(|(name, _)| CardIdentifier { name, id: None })
+            .collect::<Vec<_>>()
+    };
+    identifiers.sort_by(|a, b| a.name.cmp(&b.name));
+    identifiers.dedup_by(|a, b| a.name == b.name);
+    if identifiers.is_empty() {
+        return Ok(());
+    }
+
+    let lookup = fetch_collection(identifiers, options.timeout, options.image_quality).await?;
+    let mut cache = lookup_cache().lock().unwrap_or_else(|e| e.into_inner());
+    for (name, card) in lookup.cards {
+        cache.insert(lookup_key(&name, None, options.image_quality), card);
+    }
+    Ok(())
+}
+
+/// Looks a card up, reusing earlier answers for the same text.
+async fn lookup_card(
+    name: &str,
+    printing: Option<&(String, String)>,
+    options: &DecklistOptions,
+) -> Result<Card> {
+    let timeout = options.timeout;
+    let key = lookup_key(name, printing, options.image_quality);
+
+    let cached = lookup_cache()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&key)
+        .cloned();
+    if let Some(card) = cached {
+        return Ok(card);
+    }
+
+    // An unknown printing still leaves the name to fall back on.
+    let card = match printing {
+        Some((set, number)) => match self::printing(set, number, timeout).await {
+            Err(Error::Scryfall(_)) => named_fuzzy(name, timeout).await,
+            r => r,
+        },
+        None => named_fuzzy(name, timeout).await,
+    }
+    .map(|c| card_from_scryfall(c, options.image_quality))?;
+
+    lookup_cache()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(key, card.clone());
+    Ok(card)
+}
+
+/// Splits a trailing Arena/MTGO printing, as in `Lightning Bolt (2XM) 129`,
+/// into the card name and its set code and collector number.
+pub fn split_printing(name: &str) -> (String, Option<(String, String)>) {
+    let tokens = name.split_whitespace().collect::<Vec<_>>();
+    if let [card_name @ .., set, number] = tokens.as_slice() {
+        let set = set
+            .strip_prefix('(')
+            .and_then(|s| s.strip_suffix(')'))
+            .filter(|s| !s.is_empty() && !card_name.is_empty());
+        if let Some(set) = set {
+            return (
+                card_name.join(" "),
+                Some((set.to_lowercase(), (*number).to_owned())),
+            );
+        }
+    }
+
+    (name.to_owned(), None)
+}
+
+/// Writes a decklist in the plain `{quantity} {name}` format Arena and MTGO
+/// import, which `process_decklist` reads back.
+pub fn format_decklist(cards: &[CardInDeck], sideboard: &[CardInDeck]) -> String {
+    let mut decklist = cards
+        .iter()
+        .map(|c| format!("{} {}\n", c.quantity, c.card.name))
+        .collect::<String>();
+
+    if !sideboard.is_empty() {
+        decklist.push_str("\nSideboard\n");
+        for c in sideboard {
+            decklist.push_str(&format!("{} {}\n", c.quantity, c.card.name));
+        }
+    }
+
+    decklist
+}
+
+/// Looks each card up again by exact name and returns the image URL Scryfall
+/// currently serves for it. Cards that fail to resolve are left out. The
+/// lookup cache is skipped, and updated with the fresh answers.
+pub async fn refresh_image_urls(
+    card_names: Vec<String>,
+    timeout: Duration,
+    quality: ImageQuality,
+) -> HashMap<String, Vec<String>> {
+    let identifiers = card_names
+        .into_iter()
+        .map(|name| CardIdentifier { name, id: None })
+        .collect();
+    let cards = match fetch_collection(identifiers, timeout, quality).await {
+        Ok(lookup) => lookup.cards,
+        Err(_) => return HashMap::new(),
+    };
+
+    {
+        let mut cache = lookup_cache().lock().unwrap_or_else(|e| e.into_inner());
+        for (name, card) in &cards {
+            cache.insert(lookup_key(name, None, quality), card.clone());
+        }
+    }
+
+    cards
+        .into_iter()
+        .filter(|(_, c)| !c.img.is_empty())
+        .map(|(name, c)| {
+            let mut candidates = vec![c.img];
+            candidates.extend(c.img_fallbacks);
+            (name, candidates)
+        })
+        .collect()
+}
+
+fn card_from_scryfall(c: scryfall::Card, quality: ImageQuality) -> Card {
+    let mut img_fallbacks = card_image_urls(&c, quality);
+    let img = if img_fallbacks.is_empty() {
+        String::new()
+    } else {
+        img_fallbacks.remove(0)
+    };
+
+    let back_img = match c.image_uris {
+        Some(_) => None,
+        None => face_image_urls(&c, 1, quality).into_iter().next(),
+    };
+    let large_img = match &c.image_uris {
+        Some(imgs) => imgs.large.as_ref().map(|url| url.to_string()),
+        None => c
+            .card_faces
+            .as_ref()
+            .and_then(|faces| faces.first())
+            .and_then(|f| f.image_uris.as_ref())
+            .and_then(|imgs| imgs.get("large"))
+            .map(|url| url.to_string()),
+    };
+
+    Card {
+        id: Some(c.id),
+        img,
+        img_fallbacks,
+        back_img,
+        large_img,
+        legalities: card_legalities(&c),
+        set: Some(c.set.to_string()),
+        name: c.name,
+        type_line: c.type_line,
+        usd_price: c.prices.usd,
+        cmc: c.cmc,
+        mana_cost: c.mana_cost,
+        colors: c
+            .colors
+            .map(|colors| colors.iter().map(|color| color.to_string()).collect()),
+    }
+}
+
+fn card_legalities(card: &scryfall::Card) -> HashMap<GameFormat, Legality> {
+    GameFormat::ALL
+        .iter()
+        .map(|&format| {
+            let scryfall_format = match format {
+                GameFormat::Standard => scryfall::format::Format::Standard,
+                GameFormat::Pioneer => scryfall::format::Format::Pioneer,
+                GameFormat::Modern => scryfall::format::Format::Modern,
+                GameFormat::Legacy => scryfall::format::Format::Legacy,
+                GameFormat::Vintage => scryfall::format::Format::Vintage,
+                GameFormat::Pauper => scryfall::format::Format::Pauper,
+                GameFormat::Commander => scryfall::format::Format::Commander,
+            };
+            let legality = match card.legalities[scryfall_format] {
+                scryfall::card::Legality::Legal => Legality::Legal,
+                scryfall::card::Legality::NotLegal => Legality::NotLegal,
+                scryfall::card::Legality::Restricted => Legality::Restricted,
+                scryfall::card::Legality::Banned => Legality::Banned,
+            };
+            (format, legality)
+        })
+        .collect()
+}
+
+/// Candidate image URLs, the preferred size first.
+/// Double-faced cards have no top-level images, so their front face is used.
+fn card_image_urls(card: &scryfall::Card, quality: ImageQuality) -> Vec<String> {
+    let imgs = match &card.image_uris {
+        Some(imgs) => imgs,
+        None => return face_image_urls(card, 0, quality),
+    };
+
+    quality
+        .sizes()
+        .into_iter()
+        .filter_map(|size| match size {
+            "small" => imgs.small.as_ref(),
+            "normal" => imgs.normal.as_ref(),
+            "large" => imgs.large.as_ref(),
+            "png" => imgs.png.as_ref(),
+            _ => imgs.art_crop.as_ref(),
+        })
+        .map(|url| url.to_string())
+        .collect()
+}
+
+fn face_image_urls(card: &scryfall::Card, face: usize, quality: ImageQuality) -> Vec<String> {
+    let imgs = match card
+        .card_faces
+        .as_ref()
+        .and_then(|faces| faces.get(face))
+        .and_then(|f| f.image_uris.as_ref())
+    {
+        Some(imgs) => imgs,
+        None => return Vec::new(),
+    };
+
+    quality
+        .sizes()
+        .into_iter()
+        .filter_map(|size| imgs.get(size))
+        .map(|url| url.to_string())
+        .collect()
+}
+
+/// HTTP client shared by every request to Scryfall so connections are pooled.
+fn client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+
+    CLIENT.get_or_init(|| {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::ACCEPT,
+            HeaderValue::from_static("application/json;q=0.9,*/*;q=0.8"),
+        );
+
+        reqwest::Client::builder()
+            .user_agent(USER_AGENT)
+            .default_headers(headers)
+            .connect_timeout(Duration::from_secs(10))
+            .timeout(Duration::from_secs(30))
+            .build()
+            .expect("failed to build HTTP client")
+    })
+}
+
+/// Official rulings for a card, oldest first. Cards stored before ids were
+/// kept are looked up by exact name first.
+pub async fn rulings(name: &str, id: Option<Uuid>, timeout: Duration) -> Result<Vec<String>> {
+    let id = match id {
+        Some(id) => id,
+        None => named(name, timeout).await?.id,
+    };
+
+    let rulings: List<Ruling> = get_json(&format!("/cards/{}/rulings", id), &[], timeout).await?;
+
+    Ok(rulings
+        .data
+        .into_iter()
+        .map(|r| format!("{}: {}", r.published_at, r.comment))
+        .collect())
+}
+
+/// Best token or emblem printing matching `name`.
+pub async fn token_fuzzy(name: &str, timeout: Duration, quality: ImageQuality) -> Result<Card> {
+    let query = format!("{} (t:token or t:emblem)", name);
+    let tokens: List<scryfall::Card> = get_json("/cards/search", &[("q", &query)], timeout).await?;
+
+    tokens
+        .data
+        .into_iter()
+        .next()
+        .map(|c| card_from_scryfall(c, quality))
+        .ok_or_else(|| Error::NotFound(name.to_owned()))
+}
+
+/// Scryfall accepts at most this many cards per collection request.
+const COLLECTION_BATCH_SIZE: usize = 75;
+
+#[derive(Clone, Debug)]
+pub struct CardIdentifier {
+    pub name: String,
+    pub id: Option<Uuid>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CollectionLookup {
+    /// Resolved cards, keyed by the name they were requested with.
+    pub cards: HashMap<String, Card>,
+    pub errors: Vec<CardErrorInsight>,
+}
+
+/// Resolves many cards through Scryfall's collection endpoint, up to
+/// `COLLECTION_BATCH_SIZE` per request. Cards are looked up by id when known
+/// and by exact name otherwise. A failed batch or a card Scryfall can't find
+/// is reported in `errors` without dropping the rest; losing the connection
+/// aborts the whole lookup.
+pub async fn fetch_collection(
+    identifiers: Vec<CardIdentifier>,
+    timeout: Duration,
+    quality: ImageQuality,
+) -> Result<CollectionLookup> {
+    let mut lookup = CollectionLookup::default();
+
+    for batch in identifiers.chunks(COLLECTION_BATCH_SIZE) {
+        let body = batch
+            .iter()
+            .map(|c| match c.id {
+                Some(id) => serde_json::json!({ "id": id }),
+                None => serde_json::json!({ "name": c.name }),
+            })
+            .collect::<Vec<_>>();
+        let found: List<scryfall::Card> = match post_json(
+            "/cards/collection",
+            &serde_json::json!({ "identifiers": body }),
+            timeout,
+        )
+        .await
+        {
+            Ok(found) => found,
+            Err(e @ Error::Network(_)) => return Err(e),
+            Err(e) => {
+                lookup.errors.extend(
+                    batch
+                        .iter()
+                        .map(|c| CardErrorInsight::from_error(&c.name, &e)),
+                );
+                continue;
+            }
+        };
+
+        let mut resolved = found
+            .data
+            .into_iter()
+            .map(|c| card_from_scryfall(c, quality))
+            .collect::<Vec<_>>();
+        for requested in batch {
+            let position = resolved.iter().position(|c| match requested.id {
+                Some(id) => c.id == Some(id),
+                None => c.name.eq_ignore_ascii_case(&requested.name),
+            });
+            match position {
+                Some(p) => {
+                    lookup
+                        .cards
+                        .insert(requested.name.clone(), resolved.swap_remove(p));
+                }
+                None => lookup
+                    .errors
+                    .push(CardErrorInsight::not_found(&requested.name)),
+            }
+        }
+    }
+
+    Ok(lookup)
+}
+
+pub async fn card_named(name: &str, timeout: Duration, quality: ImageQuality) -> Result<Card> {
+    named(name, timeout)
+        .await
+        .map(|c| card_from_scryfall(c, quality))
+}
+
+pub async fn printing(set: &str, number: &str, timeout: Duration) -> Result<scryfall::Card> {
+    get_json(&format!("/cards/{}/{}", set, number), &[], timeout).await
+}
+
+pub async fn named_fuzzy(name: &str, timeout: Duration) -> Result<scryfall::Card> {
+    get_json("/cards/named", &[("fuzzy", name)], timeout).await
+}
+
+pub async fn named(name: &str, timeout: Duration) -> Result<scryfall::Card> {
+    get_json("/cards/named", &[("exact", name)], timeout).await
+}
 
-        match scryfall::Card::named_fuzzy(&card.1).await {
-            Ok(c) => cards_in_deck.push(CardInDeck {
-                quantity: card.0,
-                current_quantity: 0,
-                card: Card {
-                    name: c.name,
-                    img: c
-                        .image_uris
-                        .map(|imgs| {
-                            imgs.small
-                                .or(imgs.png)
-                                .map(|url| url.to_string())
-                                .unwrap_or(String::default())
-                        })
-                        .unwrap_or(String::default()),
-                },
-            }),
-            Err(e) => errors.push(CardErrorInsight::new(&card.1, &e.to_string())),
+async fn get_json<T: DeserializeOwned>(
+    path: &str,
+    query: &[(&str, &str)],
+    timeout: Duration,
+) -> Result<T> {
+    let request = client()
+        .get(format!("{}{}", API_URL, path))
+        .query(query)
+        .timeout(timeout);
+    send_json(request).await
+}
+
+async fn post_json<T: DeserializeOwned>(
+    path: &str,
+    body: &serde_json::Value,
+    timeout: Duration,
+) -> Result<T> {
+    let request = client()
+        .post(format!("{}{}", API_URL, path))
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(body.to_string())
+        .timeout(timeout);
+    send_json(request).await
+}
+
+/// Caps how many requests are sent to Scryfall per second. Scryfall asks
+/// clients to stay at or below 10.
+pub fn set_rate_limit(requests_per_second: u32) {
+    REQUESTS_PER_SECOND.store(requests_per_second.max(1), Ordering::Relaxed);
+}
+
+/// Waits for the next free request slot. Slots are handed out in order, so
+/// concurrent callers queue up instead of bursting.
+async fn throttle() {
+    static NEXT_SLOT: OnceLock<Mutex<Instant>> = OnceLock::new();
+
+    let interval = Duration::from_secs(1) / REQUESTS_PER_SECOND.load(Ordering::Relaxed);
+    let wait = {
+        let mut next = NEXT_SLOT
+            .get_or_init(|| Mutex::new(Instant::now()))
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        let slot = (*next).max(now);
+        *next = slot + interval;
+        slot - now
+    };
+
+    tokio::time::sleep(wait).await;
+}
+
+/// Sends a throttled request, retrying with exponential backoff while
+/// Scryfall answers 429 Too Many Requests.
+async fn send(request: reqwest::RequestBuilder) -> Result<reqwest::Response> {
+    let mut backoff = RETRY_BACKOFF;
+
+    for _ in 1..MAX_ATTEMPTS {
+        let retry = match request.try_clone() {
+            Some(r) => r,
+            None => break,
         };
+        throttle().await;
+        let response = retry.send().await.map_err(request_error)?;
+        if response.status() != reqwest::StatusCode::TOO_MANY_REQUESTS {
+            return Ok(response);
+        }
+
+        tokio::time::sleep(backoff).await;
+        backoff *= 2;
+    }
+
+    throttle().await;
+    request.send().await.map_err(request_error)
+}
+
+/// Downloads a file, such as a card image, through the shared throttle.
+pub async fn download(url: &str, timeout: Duration) -> Result<Bytes> {
+    let response = send(client().get(url).timeout(timeout)).await?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(Error::Http(status));
+    }
+
+    response.bytes().await.map_err(request_error)
+}
+
+async fn send_json<T: DeserializeOwned>(request: reqwest::RequestBuilder) -> Result<T> {
+    let response = send(request).await?;
+
+    let status = response.status();
+    let body = response.bytes().await.map_err(request_error)?;
+
+    if status.is_client_error() || status.is_server_error() {
+        return Err(match serde_json::from_slice(&body) {
+            Ok(error) => Error::Scryfall(error),
+            Err(_) => Error::Http(status),
+        });
+    }
+
+    Ok(serde_json::from_slice(&body)?)
+}
+
+fn request_error(error: reqwest::Error) -> Error {
+    if error.is_timeout() {
+        Error::Timeout
+    } else {
+        Error::Network(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE_DECKLIST: &str = include_str!("../example.txt");
+
+    #[test]
+    fn example_decklist_is_all_main_deck() {
+        let lines = parse_decklist(EXAMPLE_DECKLIST, false);
+        assert!(lines.iter().all(|(_, _, in_sideboard)| !in_sideboard));
+        assert_eq!(lines.iter().map(|(q, _, _)| q).sum::<i32>(), 100);
+        assert!(lines.iter().any(|(_, name, _)| name == "Inga and Esika"));
+    }
+
+    fn card_in_deck(quantity: i32, name: &str, type_line: &str) -> CardInDeck {
+        CardInDeck {
+            quantity,
+            current_quantity: 0,
+            card: Card {
+                name: name.to_owned(),
+                img: String::new(),
+                id: None,
+                type_line: Some(type_line.to_owned()),
+                set: None,
+                usd_price: None,
+                cmc: None,
+                mana_cost: None,
+                colors: None,
+                legalities: HashMap::new(),
+                img_fallbacks: Vec::new(),
+                back_img: None,
+                large_img: None,
+            },
+            conditions: Vec::new(),
+            tags: Vec::new(),
+            note: None,
+        }
+    }
+
+    #[test]
+    fn example_decklist_is_a_legal_commander_deck() {
+        let cards = parse_decklist(EXAMPLE_DECKLIST, false)
+            .into_iter()
+            .map(|(quantity, name, _)| {
+                let type_line = match name.as_str() {
+                    "Forest" | "Island" => "Basic Land — Forest",
+                    _ => "Creature",
+                };
+                card_in_deck(quantity, &name, type_line)
+            })
+            .collect::<Vec<_>>();
+
+        let violations = format_violations(&cards, GameFormat::Commander);
+        assert!(
+            violations.is_empty(),
+            "{:?}",
+            violations.iter().map(|v| &v.error).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn commander_deck_size_is_checked() {
+        let cards = vec![
+            card_in_deck(1, "Inga and Esika", "Legendary Creature"),
+            card_in_deck(97, "Forest", "Basic Land — Forest"),
+        ];
+        let violations = format_violations(&cards, GameFormat::Commander);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].card_name, "Deck");
+    }
+
+    #[test]
+    fn sideboard_starts_at_header() {
+        let lines = parse_decklist(
+            "Deck\n4 Lightning Bolt\n\n// burn\n20 Mountain\n\nSideboard:\n2 Pyroblast\n",
+            false,
+        );
+        assert_eq!(
+            lines,
+            vec![
+                (4, "Lightning Bolt".to_owned(), false),
+                (20, "Mountain".to_owned(), false),
+                (2, "Pyroblast".to_owned(), true),
+            ]
+        );
+    }
+
+    #[test]
+    fn trailing_block_is_an_unmarked_sideboard() {
+        let lines = parse_decklist("4 Lightning Bolt\n20 Mountain\n\n2 Pyroblast\n", false);
+        assert_eq!(
+            lines,
+            vec![
+                (4, "Lightning Bolt".to_owned(), false),
+                (20, "Mountain".to_owned(), false),
+                (2, "Pyroblast".to_owned(), true),
+            ]
+        );
+
+        // Too big for a sideboard.
+        let lines = parse_decklist("4 Lightning Bolt\n\n20 Mountain\n", false);
+        assert!(lines.iter().all(|(_, _, in_sideboard)| !in_sideboard));
+
+        // A header decides on its own.
+        let lines = parse_decklist(
+            "4 Lightning Bolt\n\n2 Shock\nSideboard\n2 Pyroblast\n",
+            false,
+        );
+        assert_eq!(
+            lines.iter().map(|(_, _, s)| *s).collect::<Vec<_>>(),
+            [false, false, true]
+        );
+    }
+
+    #[test]
+    fn parse_line_reads_leading_quantity() {
+        assert_eq!(
+            parse_line("4 Lightning Bolt", false),
+            (4, "Lightning Bolt".to_owned())
+        );
+        assert_eq!(
+            parse_line("4x Lightning Bolt", false),
+            (4, "Lightning Bolt".to_owned())
+        );
+        assert_eq!(
+            parse_line("  2X   Island ", false),
+            (2, "Island".to_owned())
+        );
+    }
+
+    #[test]
+    fn parse_line_without_quantity() {
+        assert_eq!(
+            parse_line("Lightning Bolt", true),
+            (1, "Lightning Bolt".to_owned())
+        );
+        assert_eq!(parse_line("   Lightning Bolt", true).1, "Lightning Bolt");
+    }
+
+    #[test]
+    fn missing_quantity_setting() {
+        assert_eq!(parse_line("Sol Ring", true), (1, "Sol Ring".to_owned()));
+        // Quantity 0 is reported as an invalid quantity.
+        assert_eq!(parse_line("Sol Ring", false).0, 0);
+        // Lines with a quantity read the same either way.
+        assert_eq!(
+            parse_line("1 Sol Ring", true),
+            parse_line("1 Sol Ring", false)
+        );
+    }
+
+    #[test]
+    fn parse_line_rejects_malformed_quantities() {
+        assert_eq!(parse_line("x Forest", false).0, 0);
+        assert_eq!(parse_line("4y Forest", false).0, 0);
+        assert_eq!(parse_line("four Forest", false).0, 0);
+        // Taken as part of the name when missing quantities default to 1.
+        assert_eq!(parse_line("4y Forest", true), (1, "4y Forest".to_owned()));
+    }
+
+    #[test]
+    fn split_printing_accepts_set_and_number() {
+        assert_eq!(
+            split_printing("Lightning Bolt (2XM) 129"),
+            (
+                "Lightning Bolt".to_owned(),
+                Some(("2xm".to_owned(), "129".to_owned()))
+            )
+        );
+        assert_eq!(
+            split_printing("Forest (ZEN) 246a").1,
+            Some(("zen".to_owned(), "246a".to_owned()))
+        );
+    }
+
+    #[test]
+    fn split_printing_rejects_partial_forms() {
+        let unchanged = |entry: &str| (entry.to_owned(), None);
+        // A set code alone isn't enough to pick a printing.
+        assert_eq!(
+            split_printing("Lightning Bolt (2xm)"),
+            unchanged("Lightning Bolt (2xm)")
+        );
+        assert_eq!(
+            split_printing("Lightning Bolt 2XM 129"),
+            unchanged("Lightning Bolt 2XM 129")
+        );
+        assert_eq!(
+            split_printing("Lightning Bolt () 129"),
+            unchanged("Lightning Bolt () 129")
+        );
+        assert_eq!(split_printing("(2XM) 129"), unchanged("(2XM) 129"));
+        assert_eq!(
+            split_printing("Lightning Bolt"),
+            unchanged("Lightning Bolt")
+        );
     }
 
-    (cards_in_deck, errors)
+    #[test]
+    fn lookup_keys_depend_on_image_quality() {
+        let printing = ("2xm".to_owned(), "129".to_owned());
+        assert_eq!(
+            lookup_key("Lightning  Bolt", Some(&printing), ImageQuality::Large),
+            "Large:lightning bolt (2xm) 129"
+        );
+        assert_ne!(
+            lookup_key("Lightning Bolt", None, ImageQuality::Small),
+            lookup_key("Lightning Bolt", None, ImageQuality::Normal)
+        );
+    }
+
+    #[test]
+    fn duplicate_lines_merge() {
+        let mut section = Vec::new();
+        merge_card(
+            &mut section,
+            card_in_deck(2, "Forest", "Basic Land — Forest"),
+        );
+        merge_card(
+            &mut section,
+            card_in_deck(1, "Island", "Basic Land — Island"),
+        );
+        merge_card(
+            &mut section,
+            card_in_deck(2, "Forest", "Basic Land — Forest"),
+        );
+
+        assert_eq!(section.len(), 2);
+        assert_eq!(section[0].card.name, "Forest");
+        assert_eq!(section[0].quantity, 4);
+        assert_eq!(section[1].quantity, 1);
+    }
+
+    #[test]
+    fn bare_quantity_is_missing_a_card_name() {
+        let lines = parse_decklist("4\n2 Island", true);
+        assert_eq!(lines[0], (4, String::new(), false));
+
+        let error = line_error(lines[0].0, &lines[0].1).unwrap();
+        assert_eq!(error.card_name, "4");
+        assert_eq!(error.error, "Missing card name");
+        assert!(line_error(lines[1].0, &lines[1].1).is_none());
+        assert_eq!(line_error(0, "Island").unwrap().error, "Invalid quantity");
+    }
+
+    fn error_kind(error: Error) -> ErrorKind {
+        CardErrorInsight::from_error("Lightning Bolt", &error).kind
+    }
+
+    fn scryfall_error(code: &str, error_type: Option<&str>) -> Error {
+        Error::Scryfall(ScryfallError {
+            status: 404,
+            code: code.to_owned(),
+            details: "details".to_owned(),
+            error_type: error_type.map(str::to_owned),
+            warnings: Vec::new(),
+        })
+    }
+
+    #[test]
+    fn errors_map_to_kinds() {
+        let network = reqwest::Client::new().get("not a url").build().unwrap_err();
+        let json = serde_json::from_str::<Card>("{").unwrap_err();
+
+        assert_eq!(error_kind(Error::Timeout), ErrorKind::Network);
+        assert_eq!(error_kind(Error::Network(network)), ErrorKind::Network);
+        assert_eq!(
+            error_kind(Error::NotFound("Lightning Bolt".to_owned())),
+            ErrorKind::NotFound
+        );
+        assert_eq!(
+            error_kind(Error::Http(reqwest::StatusCode::BAD_GATEWAY)),
+            ErrorKind::Network
+        );
+        assert_eq!(error_kind(Error::Json(json)), ErrorKind::Network);
+        assert_eq!(
+            error_kind(scryfall_error("not_found", None)),
+            ErrorKind::NotFound
+        );
+        assert_eq!(
+            error_kind(scryfall_error("not_found", Some("ambiguous"))),
+            ErrorKind::Ambiguous
+        );
+        assert_eq!(
+            error_kind(scryfall_error("bad_request", None)),
+            ErrorKind::Invalid
+        );
+    }
 }
@@ -0,0 +1,94 @@
+use std::path::{Path, PathBuf};
+
+use bytes::Bytes;
+use image::{imageops, ImageResult, Rgba, RgbaImage};
+
+use crate::models::PaperSize;
+
+/// Resolution the pages are rendered at.
+const DPI: u32 = 300;
+/// A standard 2.5" x 3.5" card at `DPI`.
+const CARD_WIDTH: u32 = DPI * 5 / 2;
+const CARD_HEIGHT: u32 = DPI * 7 / 2;
+const PLACEHOLDER_BORDER: u32 = 6;
+
+fn page_pixels(paper: PaperSize) -> (u32, u32) {
+    match paper {
+        // 8.5" x 11"
+        PaperSize::Letter => (DPI * 17 / 2, DPI * 11),
+        // 210mm x 297mm
+        PaperSize::A4 => (DPI * 210 * 10 / 254, DPI * 297 * 10 / 254),
+    }
+}
+
+/// Tiles card images at real size onto as many pages as needed, centered on
+/// each page. Cards without an image, or with one that can't be decoded, get
+/// a blank placeholder cell so the sheet still has the right card count.
+pub fn render_pages(cards: &[Option<Bytes>], paper: PaperSize) -> Vec<RgbaImage> {
+    let (width, height) = page_pixels(paper);
+    let columns = width / CARD_WIDTH;
+    let rows = height / CARD_HEIGHT;
+    let left = (width - columns * CARD_WIDTH) / 2;
+    let top = (height - rows * CARD_HEIGHT) / 2;
+
+    cards
+        .chunks((columns * rows) as usize)
+        .map(|page_cards| {
+            let mut page = RgbaImage::from_pixel(width, height, Rgba([255, 255, 255, 255]));
+            for (i, card) in page_cards.iter().enumerate() {
+                let i = i as u32;
+                let x = left + (i % columns) * CARD_WIDTH;
+                let y = top + (i / columns) * CARD_HEIGHT;
+                let cell = card
+                    .as_ref()
+                    .and_then(|bytes| image::load_from_memory(bytes).ok())
+                    .map(|img| {
+                        imageops::resize(
+                            &img,
+                            CARD_WIDTH,
+                            CARD_HEIGHT,
+                            imageops::FilterType::Lanczos3,
+                        )
+                    })
+                    .unwrap_or_else(placeholder);
+                imageops::overlay(&mut page, &cell, x.into(), y.into());
+            }
+            page
+        })
+        .collect()
+}
+
+/// Saves the pages next to `path`, numbered when there's more than one, and
+/// returns where they were written.
+pub fn save_pages(pages: &[RgbaImage], path: &Path) -> ImageResult<Vec<PathBuf>> {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "proxies".to_owned());
+
+    let mut written = Vec::new();
+    for (i, page) in pages.iter().enumerate() {
+        let file = if pages.len() == 1 {
+            path.with_file_name(format!("{}.png", stem))
+        } else {
+            path.with_file_name(format!("{}-{}.png", stem, i + 1))
+        };
+        page.save(&file)?;
+        written.push(file);
+    }
+    Ok(written)
+}
+
+fn placeholder() -> RgbaImage {
+    RgbaImage::from_fn(CARD_WIDTH, CARD_HEIGHT, |x, y| {
+        let border = x < PLACEHOLDER_BORDER
+            || y < PLACEHOLDER_BORDER
+            || x >= CARD_WIDTH - PLACEHOLDER_BORDER
+            || y >= CARD_HEIGHT - PLACEHOLDER_BORDER;
+        if border {
+            Rgba([80, 80, 80, 255])
+        } else {
+            Rgba([220, 220, 220, 255])
+        }
+    })
+}
@@ -0,0 +1,137 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    models::{ImageQuality, PaperSize},
+    mtg::DecklistOptions,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub refresh_images_on_import: bool,
+    pub default_missing_quantity: bool,
+    pub request_timeout_secs: u32,
+    pub requests_per_second: u32,
+    pub thumbnail_height: u16,
+    /// Pause after the last keystroke before searching, in milliseconds.
+    pub search_debounce_ms: u32,
+    /// Search results shown at first, and added by each "Show more".
+    pub search_results: u32,
+    /// Memory kept for card images, in megabytes.
+    pub image_cache_mb: u32,
+    pub auto_refresh_prices: bool,
+    pub dark_theme: bool,
+    /// Size of the images saved with newly resolved cards.
+    pub image_quality: ImageQuality,
+    pub paper_size: PaperSize,
+    /// Decklist names, lowercased, mapped to the name looked up instead.
+    pub name_corrections: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Setting {
+    RefreshImagesOnImport(bool),
+    DefaultMissingQuantity(bool),
+    RequestTimeout(u32),
+    RequestsPerSecond(u32),
+    ThumbnailHeight(u16),
+    SearchDebounce(u32),
+    SearchResults(u32),
+    ImageCacheSize(u32),
+    AutoRefreshPrices(bool),
+    DarkTheme(bool),
+    ImageQuality(ImageQuality),
+    PaperSize(PaperSize),
+    AddNameCorrection(String, String),
+    RemoveNameCorrection(String),
+}
+
+impl Setting {
+    /// Whether the value comes from a slider, which sends every step while
+    /// it's dragged.
+    pub fn is_dragged(&self) -> bool {
+        matches!(
+            self,
+            Setting::RequestTimeout(_)
+                | Setting::RequestsPerSecond(_)
+                | Setting::ThumbnailHeight(_)
+                | Setting::ImageCacheSize(_)
+                | Setting::SearchDebounce(_)
+                | Setting::SearchResults(_)
+        )
+    }
+}
+
+impl Settings {
+    pub fn apply(&mut self, setting: Setting) {
+        match setting {
+            Setting::RefreshImagesOnImport(value) => self.refresh_images_on_import = value,
+            Setting::DefaultMissingQuantity(value) => self.default_missing_quantity = value,
+            Setting::RequestTimeout(value) => self.request_timeout_secs = value,
+            Setting::RequestsPerSecond(value) => self.requests_per_second = value,
+            Setting::ThumbnailHeight(value) => self.thumbnail_height = value,
+            Setting::SearchDebounce(value) => self.search_debounce_ms = value,
+            Setting::SearchResults(value) => self.search_results = value,
+            Setting::ImageCacheSize(value) => self.image_cache_mb = value,
+            Setting::AutoRefreshPrices(value) => self.auto_refresh_prices = value,
+            Setting::DarkTheme(value) => self.dark_theme = value,
+            Setting::ImageQuality(value) => self.image_quality = value,
+            Setting::PaperSize(value) => self.paper_size = value,
+            Setting::AddNameCorrection(from, to) => {
+                self.name_corrections
+                    .insert(from.trim().to_lowercase(), to.trim().to_owned());
+            }
+            Setting::RemoveNameCorrection(from) => {
+                self.name_corrections.remove(&from);
+            }
+        }
+    }
+
+    pub fn decklist_options(&self) -> DecklistOptions {
+        DecklistOptions {
+            default_missing_quantity: self.default_missing_quantity,
+            timeout: self.request_timeout(),
+            name_corrections: self.name_corrections.clone(),
+            format: None,
+            image_quality: self.image_quality,
+        }
+    }
+
+    pub fn image_cache_budget(&self) -> usize {
+        self.image_cache_mb as usize * 1024 * 1024
+    }
+
+    pub fn search_debounce(&self) -> Duration {
+        Duration::from_millis(self.search_debounce_ms.into())
+    }
+
+    pub fn search_results(&self) -> usize {
+        self.search_results as usize
+    }
+
+    pub fn request_timeout(&self) -> Duration {
+        Duration::from_secs(self.request_timeout_secs.into())
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            refresh_images_on_import: false,
+            default_missing_quantity: true,
+            request_timeout_secs: 30,
+            requests_per_second: 10,
+            thumbnail_height: 100,
+            search_debounce_ms: 250,
+            search_results: 10,
+            image_cache_mb: 200,
+            auto_refresh_prices: false,
+            dark_theme: true,
+            image_quality: ImageQuality::default(),
+            paper_size: PaperSize::default(),
+            name_corrections: BTreeMap::new(),
+        }
+    }
+}
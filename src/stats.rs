@@ -0,0 +1,750 @@
+use std::collections::HashMap;
+
+use rand::seq::SliceRandom;
+use uuid::Uuid;
+
+use crate::models::{Card, CardInDeck, Deck, GameFormat, IndexedCard, Legality};
+
+pub const OPENING_HAND_SIZE: usize = 7;
+pub const SIMULATION_RUNS: usize = 10_000;
+
+#[derive(Debug, Clone)]
+pub struct HandSimulation {
+    pub runs: usize,
+    pub deck_size: usize,
+    pub lands_2_to_4: f32,
+    pub lands_0_to_1: f32,
+    pub lands_5_plus: f32,
+    pub target: Option<TargetOdds>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TargetOdds {
+    pub card_name: String,
+    pub turn: usize,
+    pub probability: f32,
+}
+
+pub fn is_land(card: &Card) -> bool {
+    card.type_line
+        .as_ref()
+        .map(|t| t.contains("Land"))
+        .unwrap_or(false)
+}
+
+pub const MANA_CURVE_LABELS: [&str; 9] = ["0", "1", "2", "3", "4", "5", "6", "7+", "N/A"];
+
+/// Copies of nonland cards per mana value, matching `MANA_CURVE_LABELS`.
+/// Cards without a known mana value go in the last bucket.
+pub fn mana_curve(cards: &[CardInDeck]) -> [i32; 9] {
+    let mut curve = [0; 9];
+    for card in cards.iter().filter(|c| !is_land(&c.card)) {
+        let bucket = match card.card.cmc {
+            Some(cmc) => (cmc as usize).min(7),
+            None => 8,
+        };
+        curve[bucket] += card.quantity;
+    }
+    curve
+}
+
+const COLOR_ORDER: [&str; 5] = ["W", "U", "B", "R", "G"];
+
+/// Colors across the deck's cards in WUBRG order, or `["C"]` when every card
+/// with color data is colorless. Cards without color data are skipped.
+pub fn deck_colors(cards: &[CardInDeck]) -> Vec<&'static str> {
+    let known = cards
+        .iter()
+        .filter_map(|c| c.card.colors.as_ref())
+        .collect::<Vec<_>>();
+
+    let colors = COLOR_ORDER
+        .into_iter()
+        .filter(|color| known.iter().any(|colors| colors.iter().any(|c| c == color)))
+        .collect::<Vec<_>>();
+
+    if colors.is_empty() && !known.is_empty() {
+        vec!["C"]
+    } else {
+        colors
+    }
+}
+
+pub fn land_count(cards: &[CardInDeck]) -> i32 {
+    cards
+        .iter()
+        .filter(|c| is_land(&c.card))
+        .fold(0, |acc, c| acc + c.quantity)
+}
+
+pub fn card_count(cards: &[CardInDeck]) -> i32 {
+    cards.iter().fold(0, |acc, c| acc + c.quantity)
+}
+
+pub const SUGGESTED_TAGS: [&str; 6] =
+    ["ramp", "removal", "draw", "wincon", "counter", "protection"];
+
+/// Copies per tag, sorted by tag name.
+pub fn tag_counts(cards: &[CardInDeck]) -> Vec<(String, i32)> {
+    let mut counts = HashMap::<&str, i32>::new();
+    for card in cards {
+        for tag in &card.tags {
+            *counts.entry(tag.as_str()).or_default() += card.quantity;
+        }
+    }
+
+    let mut counts = counts
+        .into_iter()
+        .map(|(tag, count)| (tag.to_owned(), count))
+        .collect::<Vec<_>>();
+    counts.sort();
+    counts
+}
+
+#[derive(Debug, Clone)]
+pub struct LandAdvice {
+    pub format_name: &'static str,
+    pub min: i32,
+    pub max: i32,
+}
+
+/// Typical land counts for the deck's format, guessed from the deck size
+/// when no format is set.
+pub fn recommended_lands(deck_size: i32, format: Option<GameFormat>) -> Option<LandAdvice> {
+    let (format_name, min, max) = match (format, deck_size) {
+        (Some(GameFormat::Commander), _) | (None, 99..=100) => ("Commander", 36, 38),
+        (Some(_), _) | (None, 60..=80) => ("60-card constructed", 22, 26),
+        (None, 40..=45) => ("40-card limited", 16, 18),
+        _ => return None,
+    };
+    Some(LandAdvice {
+        format_name,
+        min,
+        max,
+    })
+}
+
+const MAX_GOLDFISH_TURN: i32 = 20;
+
+/// Rough "average castable turn" for the nonland cards in the deck.
+///
+/// On the play you have seen `6 + t` cards by turn `t`, so with a land ratio
+/// `r` you expect `(6 + t) * r` lands in hand, and you can have played at most
+/// `t` of them. A card with mana value `c` is castable on the first turn where
+/// `min(t, (6 + t) * r) >= c`. The estimate is the average of that turn over
+/// every nonland copy with known mana value.
+pub fn goldfish_turn(cards: &[CardInDeck]) -> Option<f32> {
+    let total = card_count(cards);
+    let lands = land_count(cards);
+    if total == 0 || lands == 0 {
+        return None;
+    }
+    let land_ratio = lands as f32 / total as f32;
+
+    let castable_turn = |cmc: f32| {
+        (1..=MAX_GOLDFISH_TURN)
+            .find(|&t| (t as f32).min((6 + t) as f32 * land_ratio) >= cmc)
+            .unwrap_or(MAX_GOLDFISH_TURN)
+    };
+
+    let (turns, copies) = cards
+        .iter()
+        .filter(|c| !is_land(&c.card))
+        .filter_map(|c| c.card.cmc.map(|cmc| (cmc, c.quantity)))
+        .fold((0, 0), |(turns, copies), (cmc, quantity)| {
+            (turns + castable_turn(cmc) * quantity, copies + quantity)
+        });
+
+    if copies == 0 {
+        None
+    } else {
+        Some(turns as f32 / copies as f32)
+    }
+}
+
+/// Every physical copy in the deck, one entry per unit of `quantity`.
+pub fn expand_deck(cards: &[CardInDeck]) -> Vec<&Card> {
+    cards
+        .iter()
+        .flat_map(|c| std::iter::repeat_n(&c.card, c.quantity.max(0) as usize))
+        .collect()
+}
+
+/// Shuffles the deck `runs` times and looks at the opening hand plus one draw
+/// per turn after the first (on the play), which samples the hypergeometric
+/// distribution without having to compute it for every question.
+pub fn simulate_opening_hands(
+    cards: &[CardInDeck],
+    target: Option<&str>,
+    turn: usize,
+    runs: usize,
+) -> HandSimulation {
+    let mut library = expand_deck(cards);
+    let deck_size = library.len();
+    let hand_size = OPENING_HAND_SIZE.min(deck_size);
+    let seen_by_turn = (OPENING_HAND_SIZE + turn.saturating_sub(1)).min(deck_size);
+
+    let mut rng = rand::thread_rng();
+    let (mut few, mut good, mut many, mut hits) = (0, 0, 0, 0);
+
+    for _ in 0..runs {
+        let (seen, _) = library.partial_shuffle(&mut rng, seen_by_turn);
+
+        match seen[..hand_size].iter().filter(|&&c| is_land(c)).count() {
+            0..=1 => few += 1,
+            2..=4 => good += 1,
+            _ => many += 1,
+        }
+
+        if let Some(name) = target {
+            if seen.iter().any(|c| c.name == name) {
+                hits += 1;
+            }
+        }
+    }
+
+    let ratio = |n: usize| {
+        if runs == 0 {
+            0.
+        } else {
+            n as f32 / runs as f32
+        }
+    };
+
+    HandSimulation {
+        runs,
+        deck_size,
+        lands_2_to_4: ratio(good),
+        lands_0_to_1: ratio(few),
+        lands_5_plus: ratio(many),
+        target: target.map(|name| TargetOdds {
+            card_name: name.to_owned(),
+            turn,
+            probability: ratio(hits),
+        }),
+    }
+}
+
+/// One unique card summed over every deck it appears in.
+#[derive(Debug, Clone)]
+pub struct CollectionCard {
+    pub name: String,
+    pub needed: i32,
+    pub owned: i32,
+    pub deck_ids: Vec<Uuid>,
+    pub price: Option<f32>,
+    pub set: Option<String>,
+    pub type_line: Option<String>,
+}
+
+impl CollectionCard {
+    pub fn missing(&self) -> i32 {
+        (self.needed - self.owned).max(0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CardType {
+    Creature,
+    Land,
+    Instant,
+    Sorcery,
+    Artifact,
+    Enchantment,
+    Planeswalker,
+    Battle,
+}
+
+impl CardType {
+    pub const ALL: [CardType; 8] = [
+        CardType::Creature,
+        CardType::Land,
+        CardType::Instant,
+        CardType::Sorcery,
+        CardType::Artifact,
+        CardType::Enchantment,
+        CardType::Planeswalker,
+        CardType::Battle,
+    ];
+
+    pub fn matches(&self, type_line: Option<&str>) -> bool {
+        type_line
+            .map(|t| t.contains(&self.to_string()))
+            .unwrap_or(false)
+    }
+}
+
+impl std::fmt::Display for CardType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            CardType::Creature => "Creature",
+            CardType::Land => "Land",
+            CardType::Instant => "Instant",
+            CardType::Sorcery => "Sorcery",
+            CardType::Artifact => "Artifact",
+            CardType::Enchantment => "Enchantment",
+            CardType::Planeswalker => "Planeswalker",
+            CardType::Battle => "Battle",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortMode {
+    #[default]
+    Decklist,
+    Name,
+    Cmc,
+    Color,
+    Quantity,
+}
+
+impl SortMode {
+    pub const ALL: [SortMode; 5] = [
+        SortMode::Decklist,
+        SortMode::Name,
+        SortMode::Cmc,
+        SortMode::Color,
+        SortMode::Quantity,
+    ];
+}
+
+impl std::fmt::Display for SortMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            SortMode::Decklist => "Decklist order",
+            SortMode::Name => "Name (A-Z)",
+            SortMode::Cmc => "Mana value",
+            SortMode::Color => "Color",
+            SortMode::Quantity => "Quantity",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Positions of a card's colors in WUBRG order; colorless cards sort last.
+fn color_rank(card: &Card) -> Vec<usize> {
+    let colors = card.colors.as_deref().unwrap_or_default();
+    let rank = COLOR_ORDER
+        .iter()
+        .enumerate()
+        .filter(|(_, color)| colors.iter().any(|c| c == *color))
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+    if rank.is_empty() {
+        vec![COLOR_ORDER.len()]
+    } else {
+        rank
+    }
+}
+
+/// Sorts cards for display, falling back to the name on ties.
+pub fn sort_cards(cards: &mut [&CardInDeck], mode: SortMode) {
+    match mode {
+        SortMode::Decklist => {}
+        SortMode::Name => cards.sort_by(|a, b| a.card.name.cmp(&b.card.name)),
+        SortMode::Cmc => cards.sort_by(|a, b| {
+            let cmc = |c: &CardInDeck| c.card.cmc.unwrap_or(f32::MAX);
+            cmc(a)
+                .total_cmp(&cmc(b))
+                .then_with(|| a.card.name.cmp(&b.card.name))
+        }),
+        SortMode::Color => cards.sort_by(|a, b| {
+            color_rank(&a.card)
+                .cmp(&color_rank(&b.card))
+                .then_with(|| a.card.name.cmp(&b.card.name))
+        }),
+        SortMode::Quantity => cards.sort_by(|a, b| {
+            b.quantity
+                .cmp(&a.quantity)
+                .then_with(|| a.card.name.cmp(&b.card.name))
+        }),
+    }
+}
+
+/// Copies per card type, in `CardType::ALL` order and leaving out types with
+/// no cards. A card with several types counts towards each of them; cards
+/// without a known type line are counted as "Unknown".
+pub fn type_breakdown(cards: &[CardInDeck]) -> Vec<(String, i32)> {
+    let mut breakdown = CardType::ALL
+        .iter()
+        .map(|t| {
+            let count = cards
+                .iter()
+                .filter(|c| t.matches(c.card.type_line.as_deref()))
+                .fold(0, |acc, c| acc + c.quantity);
+            (t.to_string(), count)
+        })
+        .collect::<Vec<_>>();
+    let unknown = cards
+        .iter()
+        .filter(|c| c.card.type_line.is_none())
+        .fold(0, |acc, c| acc + c.quantity);
+    breakdown.push(("Unknown".to_owned(), unknown));
+
+    breakdown.retain(|(_, count)| *count > 0);
+    breakdown
+}
+
+pub fn parse_price(card: &Card) -> Option<f32> {
+    card.usd_price.as_ref().and_then(|p| p.parse().ok())
+}
+
+pub struct DeckPrice {
+    pub total: f32,
+    /// Distinct cards without a usable price, counted as zero in `total`.
+    pub unpriced: usize,
+}
+
+pub fn deck_price(cards: &[CardInDeck]) -> DeckPrice {
+    cards.iter().fold(
+        DeckPrice {
+            total: 0.,
+            unpriced: 0,
+        },
+        |mut acc, c| {
+            match parse_price(&c.card) {
+                Some(price) => acc.total += price * c.quantity as f32,
+                None => acc.unpriced += 1,
+            }
+            acc
+        },
+    )
+}
+
+pub fn aggregate_collection(
+    decks: &HashMap<Uuid, Deck>,
+    index: &[IndexedCard],
+) -> Vec<CollectionCard> {
+    let mut cards = HashMap::<&str, CollectionCard>::new();
+
+    for indexed in index {
+        let card_in_deck = match decks
+            .get(&indexed.deck_id)
+            .and_then(|d| d.cards.iter().find(|c| c.card.name == indexed.name))
+        {
+            Some(c) => c,
+            None => continue,
+        };
+
+        let entry = cards
+            .entry(indexed.name.as_str())
+            .or_insert_with(|| CollectionCard {
+                name: indexed.name.clone(),
+                needed: 0,
+                owned: 0,
+                deck_ids: Vec::new(),
+                price: parse_price(&card_in_deck.card),
+                set: card_in_deck.card.set.clone(),
+                type_line: card_in_deck.card.type_line.clone(),
+            });
+        entry.needed += card_in_deck.quantity;
+        entry.owned += card_in_deck.current_quantity;
+        if !entry.deck_ids.contains(&indexed.deck_id) {
+            entry.deck_ids.push(indexed.deck_id);
+        }
+    }
+
+    cards.into_values().collect()
+}
+
+/// One line per card with its wanted and owned copies and price, for
+/// spreadsheets.
+pub fn collection_csv(cards: &[CollectionCard]) -> String {
+    let mut csv = String::from("name,wanted,owned,price\n");
+    for c in cards {
+        let price = c.price.map(|p| format!("{:.2}", p)).unwrap_or_default();
+        csv.push_str(&format!(
+            "{},{},{},{}\n",
+            csv_field(&c.name),
+            c.needed,
+            c.owned,
+            price
+        ));
+    }
+    csv
+}
+
+/// Quotes a field holding commas, quotes or line breaks.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+/// Value of every copy marked as owned, at the stored prices.
+pub fn collection_value(cards: &[CollectionCard]) -> f32 {
+    cards
+        .iter()
+        .fold(0., |acc, c| acc + c.price.unwrap_or(0.) * c.owned as f32)
+}
+
+pub fn sparkline(values: &[f32]) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+    let min = values.iter().cloned().fold(f32::INFINITY, f32::min);
+    let max = values.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+    let range = max - min;
+
+    values
+        .iter()
+        .map(|v| {
+            if range <= 0. {
+                BARS[0]
+            } else {
+                BARS[(((v - min) / range) * (BARS.len() - 1) as f32).round() as usize]
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+pub struct Allocation {
+    pub deck_name: String,
+    pub allocated: i32,
+    pub needed: i32,
+}
+
+/// Hands out owned copies to decks in the given order, each deck taking as
+/// many as it needs before the next one gets any. Only cards present in
+/// `owned` are touched.
+pub fn allocate_owned(
+    decks: &mut HashMap<Uuid, Deck>,
+    order: &[Uuid],
+    owned: &HashMap<String, i32>,
+) -> Vec<Allocation> {
+    let mut remaining = owned.clone();
+    let mut summary = Vec::new();
+
+    for deck_id in order {
+        let deck = match decks.get_mut(deck_id) {
+            Some(d) => d,
+            None => continue,
+        };
+
+        let mut allocation = Allocation {
+            deck_name: deck.name.clone(),
+            allocated: 0,
+            needed: 0,
+        };
+        for card in deck.cards.iter_mut() {
+            if let Some(left) = remaining.get_mut(&card.card.name) {
+                let given = card.quantity.min(*left).max(0);
+                card.set_collected(given);
+                *left -= given;
+                allocation.allocated += given;
+                allocation.needed += card.quantity;
+            }
+        }
+        summary.push(allocation);
+    }
+
+    summary
+}
+
+#[derive(Debug, Clone)]
+pub struct Buildability {
+    pub deck_id: Uuid,
+    pub deck_name: String,
+    pub available: i32,
+    pub needed: i32,
+    pub shortfall: Vec<(String, i32)>,
+}
+
+impl Buildability {
+    pub fn completion(&self) -> f32 {
+        if self.needed == 0 {
+            1.
+        } else {
+            self.available as f32 / self.needed as f32
+        }
+    }
+}
+
+/// How much of each deck could be assembled from the owned pool on its own,
+/// best first.
+pub fn buildable_decks(
+    decks: &HashMap<Uuid, Deck>,
+    owned: &HashMap<String, i32>,
+) -> Vec<Buildability> {
+    let mut result = decks
+        .iter()
+        .map(|(id, deck)| {
+            let mut buildability = Buildability {
+                deck_id: *id,
+                deck_name: deck.name.clone(),
+                available: 0,
+                needed: 0,
+                shortfall: Vec::new(),
+            };
+            for card in &deck.cards {
+                let have = owned.get(&card.card.name).copied().unwrap_or(0);
+                let available = card.quantity.min(have).max(0);
+                buildability.available += available;
+                buildability.needed += card.quantity;
+                if available < card.quantity {
+                    buildability
+                        .shortfall
+                        .push((card.card.name.clone(), card.quantity - available));
+                }
+            }
+            buildability
+                .shortfall
+                .sort_by_key(|s| std::cmp::Reverse(s.1));
+            buildability
+        })
+        .collect::<Vec<_>>();
+
+    result.sort_by(|a, b| {
+        b.completion()
+            .total_cmp(&a.completion())
+            .then_with(|| a.deck_name.cmp(&b.deck_name))
+    });
+    result
+}
+
+#[derive(Debug, Clone)]
+pub struct Overcommitment {
+    pub card_name: String,
+    pub wanted: i32,
+    pub owned: i32,
+    /// Decks wanting the card, with how many copies each.
+    pub decks: Vec<(String, i32)>,
+}
+
+impl Overcommitment {
+    pub fn shortage(&self) -> i32 {
+        self.wanted - self.owned
+    }
+}
+
+/// Cards wanted by all decks together in more copies than are owned, so the
+/// decks can't all be assembled at once. Only cards present in `owned` are
+/// checked. The worst shortages come first.
+pub fn overcommitted_cards(
+    decks: &HashMap<Uuid, Deck>,
+    owned: &HashMap<String, i32>,
+) -> Vec<Overcommitment> {
+    let mut wanted: HashMap<&str, Overcommitment> = HashMap::new();
+    for deck in decks.values() {
+        for card in &deck.cards {
+            let have = match owned.get(&card.card.name) {
+                Some(have) => *have,
+                None => continue,
+            };
+            let entry = wanted
+                .entry(&card.card.name)
+                .or_insert_with(|| Overcommitment {
+                    card_name: card.card.name.clone(),
+                    wanted: 0,
+                    owned: have,
+                    decks: Vec::new(),
+                });
+            entry.wanted += card.quantity;
+            entry.decks.push((deck.name.clone(), card.quantity));
+        }
+    }
+
+    let mut result = wanted
+        .into_values()
+        .filter(|o| o.shortage() > 0)
+        .collect::<Vec<_>>();
+    for o in result.iter_mut() {
+        o.decks.sort();
+    }
+    result.sort_by(|a, b| {
+        b.shortage()
+            .cmp(&a.shortage())
+            .then_with(|| a.card_name.cmp(&b.card_name))
+    });
+    result
+}
+
+/// Cards whose legality in `format` is anything but legal.
+pub fn flagged_cards(cards: &[CardInDeck], format: GameFormat) -> Vec<(&CardInDeck, Legality)> {
+    cards
+        .iter()
+        .filter_map(|c| match c.card.legalities.get(&format) {
+            Some(&l @ (Legality::Banned | Legality::Restricted | Legality::NotLegal)) => {
+                Some((c, l))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// A short, hand-picked list of widely played cards per format.
+pub fn format_staples(format: GameFormat) -> &'static [&'static str] {
+    match format {
+        // Standard rotates too often for a bundled list to stay useful.
+        GameFormat::Standard => &[],
+        GameFormat::Pioneer => &[
+            "Fatal Push",
+            "Thoughtseize",
+            "Go for the Throat",
+            "Bloodtithe Harvester",
+            "Sheoldred, the Apocalypse",
+            "Treasure Cruise",
+        ],
+        GameFormat::Modern => &[
+            "Lightning Bolt",
+            "Thoughtseize",
+            "Fatal Push",
+            "Prismatic Ending",
+            "Solitude",
+            "Force of Negation",
+            "Urza's Saga",
+        ],
+        GameFormat::Legacy => &[
+            "Brainstorm",
+            "Force of Will",
+            "Ponder",
+            "Swords to Plowshares",
+            "Wasteland",
+            "Daze",
+            "Lightning Bolt",
+        ],
+        GameFormat::Vintage => &[
+            "Ancestral Recall",
+            "Time Walk",
+            "Force of Will",
+            "Mana Drain",
+            "Sol Ring",
+            "Mystical Tutor",
+        ],
+        GameFormat::Pauper => &[
+            "Lightning Bolt",
+            "Counterspell",
+            "Journey to Nowhere",
+            "Snuff Out",
+            "Preordain",
+            "Ash Barrens",
+        ],
+        GameFormat::Commander => &[
+            "Sol Ring",
+            "Arcane Signet",
+            "Command Tower",
+            "Swords to Plowshares",
+            "Cultivate",
+            "Swiftfoot Boots",
+            "Counterspell",
+        ],
+    }
+}
+
+/// Staples of the deck's format that the deck doesn't run.
+pub fn missing_staples(deck: &Deck) -> Vec<&'static str> {
+    let format = match deck.format {
+        Some(f) => f,
+        None => return Vec::new(),
+    };
+
+    format_staples(format)
+        .iter()
+        .filter(|&&staple| !deck.cards.iter().any(|c| c.card.name == staple))
+        .copied()
+        .collect()
+}
@@ -0,0 +1,122 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use directories::ProjectDirs;
+use serde::{de::DeserializeOwned, Serialize};
+use uuid::Uuid;
+
+use crate::{models::PriceSnapshot, settings::Settings};
+
+const PRICE_HISTORY_FILE: &str = "price_history.jsonl";
+const SETTINGS_FILE: &str = "settings.json";
+pub const OWNED_FILE: &str = "owned.json";
+pub const DECKS_FILE: &str = "decks.json";
+pub const LOOKUP_CACHE_FILE: &str = "lookup_cache.json";
+pub const PRICE_REFRESH_FILE: &str = "price_refresh.json";
+pub const UI_STATE_FILE: &str = "ui_state.json";
+
+fn project_dirs() -> Option<ProjectDirs> {
+    ProjectDirs::from("", "", "mtg-card-organizer")
+}
+
+pub fn data_dir() -> Option<PathBuf> {
+    project_dirs().map(|dirs| dirs.data_dir().to_path_buf())
+}
+
+pub fn config_dir() -> Option<PathBuf> {
+    project_dirs().map(|dirs| dirs.config_dir().to_path_buf())
+}
+
+pub fn cache_dir() -> Option<PathBuf> {
+    project_dirs().map(|dirs| dirs.cache_dir().join("images"))
+}
+
+/// Cached images are named after their URL, which changes whenever Scryfall
+/// updates the image.
+fn image_path(url: &str) -> Option<PathBuf> {
+    let key = Uuid::new_v5(&Uuid::NAMESPACE_URL, url.as_bytes());
+    cache_dir().map(|dir| dir.join(key.to_string()))
+}
+
+pub fn load_image(url: &str) -> Option<Vec<u8>> {
+    fs::read(image_path(url)?).ok()
+}
+
+pub fn save_image(url: &str, bytes: &[u8]) -> io::Result<()> {
+    let path = image_path(url).ok_or(io::ErrorKind::NotFound)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, bytes)
+}
+
+pub fn remove_image(url: &str) -> io::Result<()> {
+    let path = image_path(url).ok_or(io::ErrorKind::NotFound)?;
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Deletes every image saved on disk.
+pub fn clear_image_cache() -> io::Result<()> {
+    let dir = cache_dir().ok_or(io::ErrorKind::NotFound)?;
+    match fs::remove_dir_all(dir) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+pub fn load_settings() -> Settings {
+    config_dir()
+        .and_then(|dir| fs::read_to_string(dir.join(SETTINGS_FILE)).ok())
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_settings(settings: &Settings) -> io::Result<()> {
+    let dir = config_dir().ok_or(io::ErrorKind::NotFound)?;
+    fs::create_dir_all(&dir)?;
+    fs::write(
+        dir.join(SETTINGS_FILE),
+        serde_json::to_string_pretty(settings)?,
+    )
+}
+
+pub fn load_json<T: DeserializeOwned>(file_name: &str) -> Option<T> {
+    let contents = fs::read_to_string(data_dir()?.join(file_name)).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+pub fn save_json<T: Serialize>(file_name: &str, value: &T) -> io::Result<()> {
+    let dir = data_dir().ok_or(io::ErrorKind::NotFound)?;
+    fs::create_dir_all(&dir)?;
+    fs::write(dir.join(file_name), serde_json::to_string(value)?)
+}
+
+pub fn append_price_snapshot(snapshot: &PriceSnapshot) -> io::Result<()> {
+    let dir = data_dir().ok_or(io::ErrorKind::NotFound)?;
+    fs::create_dir_all(&dir)?;
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dir.join(PRICE_HISTORY_FILE))?;
+    writeln!(file, "{}", serde_json::to_string(snapshot)?)
+}
+
+/// Reads every snapshot recorded so far, skipping lines that don't parse.
+pub fn load_price_history() -> Vec<PriceSnapshot> {
+    let contents = match data_dir().map(|dir| fs::read_to_string(dir.join(PRICE_HISTORY_FILE))) {
+        Some(Ok(c)) => c,
+        _ => return Vec::new(),
+    };
+
+    contents
+        .lines()
+        .filter_map(|l| serde_json::from_str(l).ok())
+        .collect()
+}